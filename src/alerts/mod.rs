@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{ Deserialize, Serialize };
+use tracing::{ debug, info, warn };
+
+use crate::coins::{ self, filestore::write_data_json_to_file };
+use crate::config::alerts::{ read_default_alerts_config, AlertCondition, AlertRule };
+use crate::config::coins::read_default_coins_config;
+use crate::config::paths::Paths;
+use crate::config::read_json_config;
+use crate::config::wallets::{ read_default_wallets_config, WalletsData };
+use crate::model::{ AthInfo, Currency, PriceInfo };
+use crate::Result;
+
+mod sinks;
+
+// Triggered alert rule
+#[derive(Debug, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    // Named text, so the webhooks of chat applications show the message
+    #[serde(rename = "text")]
+    pub message: String,
+    pub value: f64,
+}
+
+// Rules which were triggered at the last check, they are notified again only after they stop being triggered
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AlertsState {
+    triggered: Vec<String>,
+}
+
+// Evaluates the rules with the latest stored prices and notifies the sinks about the newly triggered rules.
+// Rules with failed notifications are notified again at the next check.
+pub async fn check_alerts(paths: &Paths) -> Result<()> {
+    let alerts_config = read_default_alerts_config(paths)?;
+    if alerts_config.rules.is_empty() {
+        debug!("No alert rules");
+        return Ok(());
+    }
+
+    let coins_data = read_default_coins_config(paths)?;
+    let wallets = read_default_wallets_config(paths)?.resolve_aliases(&coins_data);
+    let prices = coins::get_latest_prices(paths)?;
+    let ath_data = coins::get_ath_data(paths)?;
+
+    let alerts = evaluate_rules(&alerts_config.rules, &prices, &ath_data, &wallets);
+    let state_file = paths.alerts_state_file();
+    let state = read_state(&state_file)?;
+
+    let mut triggered = Vec::new();
+    for alert in alerts.iter() {
+        if state.triggered.contains(&alert.rule) {
+            debug!(rule = %alert.rule, "Alert already notified");
+            triggered.push(alert.rule.clone());
+            continue;
+        }
+        info!(rule = %alert.rule, "Alert triggered");
+        if sinks::notify(&alerts_config.sinks, alert).await {
+            triggered.push(alert.rule.clone());
+        }
+    }
+
+    if let Some(dir) = state_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_data_json_to_file(&state_file, &AlertsState { triggered })
+}
+
+// Sends a test alert to all sinks
+pub async fn test_sinks(paths: &Paths) -> Result<()> {
+    let alerts_config = read_default_alerts_config(paths)?;
+    if alerts_config.sinks.is_empty() {
+        return Err(format!("No sinks in {}", paths.alerts_config_file().display()).into());
+    }
+    let alert = Alert {
+        rule: "test".to_string(),
+        message: "Test alert from crpt-portfolio".to_string(),
+        value: 0.0,
+    };
+    if !sinks::notify(&alerts_config.sinks, &alert).await {
+        return Err("Test alert was not sent to all sinks".into());
+    }
+    Ok(())
+}
+
+pub fn evaluate_rules(rules: &[AlertRule], prices: &[PriceInfo], ath_data: &[AthInfo], wallets: &WalletsData) -> Vec<Alert> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let alert = evaluate_rule(rule, prices, ath_data, wallets);
+            debug!(rule = %rule_name(rule), triggered = alert.is_some(), "Rule evaluated");
+            alert
+        })
+        .collect()
+}
+
+fn evaluate_rule(rule: &AlertRule, prices: &[PriceInfo], ath_data: &[AthInfo], wallets: &WalletsData) -> Option<Alert> {
+    let currency = &rule.currency;
+    let price = |coin: &str| prices.iter().find(|price| price.coin == coin && price.currency == *currency);
+
+    let (value, message) = match &rule.condition {
+        AlertCondition::PriceAbove { coin, value } => {
+            let price = price(coin)?.value;
+            (price > *value).then(|| (price, format!("{} price {:.2} {:?} is above {:.2}", coin, price, currency, value)))?
+        }
+        AlertCondition::PriceBelow { coin, value } => {
+            let price = price(coin)?.value;
+            (price < *value).then(|| (price, format!("{} price {:.2} {:?} is below {:.2}", coin, price, currency, value)))?
+        }
+        AlertCondition::Change24h { coin, percent } => {
+            let change = price(coin)?.change_24h;
+            (change.abs() > *percent).then(|| (change, format!("{} price changed by {:.2}% in 24 hours", coin, change)))?
+        }
+        AlertCondition::PortfolioAbove { value } => {
+            let total = portfolio_value(wallets, prices, currency);
+            (total > *value).then(|| (total, format!("Portfolio value {:.2} {:?} is above {:.2}", total, currency, value)))?
+        }
+        AlertCondition::PortfolioBelow { value } => {
+            let total = portfolio_value(wallets, prices, currency);
+            (total < *value).then(|| (total, format!("Portfolio value {:.2} {:?} is below {:.2}", total, currency, value)))?
+        }
+        AlertCondition::FromAth { coin, percent } => {
+            let price = price(coin)?.value;
+            let ath = ath_data.iter().find(|info| info.coin == *coin && info.currency == *currency)?.ath;
+            if ath <= 0.0 {
+                return None;
+            }
+            let below_ath = (1.0 - price / ath) * 100.0;
+            (below_ath > *percent).then(|| {
+                (below_ath, format!("{} price is {:.2}% below ATH {:.2} {:?}", coin, below_ath, ath, currency))
+            })?
+        }
+    };
+
+    Some(Alert { rule: rule_name(rule), message, value })
+}
+
+// Name of the rule, or the description of its condition
+pub fn rule_name(rule: &AlertRule) -> String {
+    if let Some(name) = &rule.name {
+        return name.clone();
+    }
+    let currency = &rule.currency;
+    match &rule.condition {
+        AlertCondition::PriceAbove { coin, value } => format!("{} price above {} {:?}", coin, value, currency),
+        AlertCondition::PriceBelow { coin, value } => format!("{} price below {} {:?}", coin, value, currency),
+        AlertCondition::Change24h { coin, percent } => format!("{} 24h change beyond {}% {:?}", coin, percent, currency),
+        AlertCondition::PortfolioAbove { value } => format!("Portfolio above {} {:?}", value, currency),
+        AlertCondition::PortfolioBelow { value } => format!("Portfolio below {} {:?}", value, currency),
+        AlertCondition::FromAth { coin, percent } => format!("{} more than {}% below ATH {:?}", coin, percent, currency),
+    }
+}
+
+// Holdings without price are not counted
+fn portfolio_value(wallets: &WalletsData, prices: &[PriceInfo], currency: &Currency) -> f64 {
+    wallets.wallets
+        .iter()
+        .flat_map(|wallet| wallet.holdings.iter())
+        .filter_map(|holding| {
+            prices
+                .iter()
+                .find(|price| price.coin == holding.coin && price.currency == *currency)
+                .map(|price| holding.amount * price.value)
+        })
+        .sum()
+}
+
+fn read_state(state_file: &Path) -> Result<AlertsState> {
+    if !state_file.exists() {
+        return Ok(AlertsState::default());
+    }
+    read_json_config(state_file).or_else(|e| {
+        warn!(error = %e, "Cannot read alerts state, all triggered alerts are notified");
+        Ok(AlertsState::default())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::alerts::AlertsConfig;
+
+    fn price(coin: &str, value: f64, change_24h: f64) -> PriceInfo {
+        PriceInfo {
+            coin: coin.to_string(),
+            currency: Currency::USD,
+            value,
+            market_cap: 0.0,
+            change_24h,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_rules() {
+        let alerts_config: AlertsConfig = serde_json::from_value(serde_json::json!({
+            "rules": [
+                { "kind": "priceAbove", "coin": "BTC", "value": 100000.0 },
+                { "kind": "priceBelow", "coin": "BTC", "value": 50000.0 },
+                { "kind": "change24h", "coin": "ETH", "percent": 10.0 },
+                { "name": "Rich", "kind": "portfolioAbove", "value": 200000.0 },
+                { "kind": "fromAth", "coin": "ETH", "percent": 30.0 },
+                { "kind": "priceAbove", "coin": "DOT", "value": 1.0 }
+            ],
+            "sinks": [{ "kind": "stdout" }, { "kind": "webhook", "url": "http://localhost:8080" }]
+        })).unwrap();
+        let wallets: WalletsData = serde_json::from_value(serde_json::json!({
+            "wallets": [{ "name": "Ledger", "kind": "Ledger", "address": "a", "holdings": [
+                { "coin": "BTC", "amount": 2.0 },
+                { "coin": "ETH", "amount": 10.0 }
+            ] }]
+        })).unwrap();
+        let prices = vec![price("BTC", 110000.0, 2.0), price("ETH", 2000.0, -12.0)];
+        let ath_data = vec![AthInfo {
+            coin: "ETH".to_string(),
+            currency: Currency::USD,
+            ath: 4000.0,
+            ath_date: "2021-11-10T14:24:19.604Z".to_string(),
+            ath_change_percentage: -50.0,
+            atl: 0.43,
+            atl_date: "2015-10-20T00:00:00.000Z".to_string(),
+            atl_change_percentage: 0.0,
+        }];
+
+        let alerts = evaluate_rules(&alerts_config.rules, &prices, &ath_data, &wallets);
+        let rules: Vec<&str> = alerts.iter().map(|alert| alert.rule.as_str()).collect();
+        assert_eq!(rules, vec!["BTC price above 100000 USD", "ETH 24h change beyond 10% USD", "Rich", "ETH more than 30% below ATH USD"]);
+        assert_eq!(alerts[2].value, 240000.0);
+        assert_eq!(alerts[3].message, "ETH price is 50.00% below ATH 4000.00 USD");
+
+        // Unnamed rules in other currencies have their own state
+        let mut eur_rule = alerts_config.rules[2].clone();
+        eur_rule.currency = Currency::EUR;
+        assert_eq!(rule_name(&eur_rule), "ETH 24h change beyond 10% EUR");
+    }
+}
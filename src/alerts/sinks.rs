@@ -0,0 +1,205 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{ AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor };
+use std::env;
+use tokio::process::Command;
+use tracing::{ debug, warn };
+
+use crate::config::alerts::{ SinkDef, SmtpSecurity };
+use crate::Result;
+
+use super::Alert;
+
+// Sends the alert to all sinks, failed sinks are logged. Returns true when all sinks were notified.
+pub async fn notify(sinks: &[SinkDef], alert: &Alert) -> bool {
+    let mut notified = true;
+    for sink in sinks {
+        if let Err(e) = send(sink, alert).await {
+            warn!(rule = %alert.rule, sink = sink_kind(sink), error = %e, "Cannot send alert");
+            notified = false;
+        }
+    }
+    notified
+}
+
+// Only the kind of the sink is logged, webhook urls and headers can contain credentials
+fn sink_kind(sink: &SinkDef) -> &'static str {
+    match sink {
+        SinkDef::Stdout => "stdout",
+        SinkDef::Command { .. } => "command",
+        SinkDef::Webhook { .. } => "webhook",
+        SinkDef::Email { .. } => "email",
+    }
+}
+
+async fn send(sink: &SinkDef, alert: &Alert) -> Result<()> {
+    match sink {
+        SinkDef::Stdout => println!("ALERT {}: {}", alert.rule, alert.message),
+        SinkDef::Command { command, args } => {
+            debug!(command, "Running alert command");
+            let status = Command::new(command)
+                .args(args)
+                .arg(&alert.message)
+                .env("CRPT_ALERT_RULE", &alert.rule)
+                .status().await
+                .map_err(|e| format!("Cannot run {}: {}", command, e))?;
+            if !status.success() {
+                return Err(format!("{} failed with {}", command, status).into());
+            }
+        }
+        SinkDef::Webhook { url, headers } => {
+            debug!("Posting alert");
+            let mut request = reqwest::Client::new().post(url).json(alert);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            // Errors of reqwest contain the url
+            request.send().await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| e.without_url())?;
+        }
+        SinkDef::Email { host, port, security, username, password_env, from, to } => {
+            debug!(host, "Sending alert email");
+            let mut message = Message::builder()
+                .from(from.parse::<Mailbox>()?)
+                .subject(format!("Crypto alert: {}", alert.rule));
+            for recipient in to {
+                message = message.to(recipient.parse::<Mailbox>()?);
+            }
+            let message = message.body(alert.message.clone())?;
+
+            let mut transport = match security {
+                SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host).port(port.unwrap_or(25)),
+                SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?.port(port.unwrap_or(587)),
+                SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?.port(port.unwrap_or(465)),
+            };
+            if let Some(username) = username {
+                let password = match password_env {
+                    Some(password_env) => env::var(password_env).map_err(|_| format!("{} not defined in env", password_env))?,
+                    None => String::new(),
+                };
+                transport = transport.credentials(Credentials::new(username.clone(), password));
+            }
+            transport.build().send(message).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{ Arc, Mutex };
+
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::{ Json, Router };
+    use serde_json::Value;
+    use tempfile::tempdir;
+    use tokio::io::{ AsyncBufReadExt, AsyncWriteExt, BufReader };
+
+    fn alert() -> Alert {
+        Alert { rule: "BTC price above 100000 USD".to_string(), message: "BTC price is 110000.00 USD".to_string(), value: 110000.0 }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_sink() {
+        type Received = Arc<Mutex<Vec<(Option<String>, Value)>>>;
+        let received: Received = Arc::default();
+        let app = Router::new()
+            .route("/hook", post(|State(received): State<Received>, headers: HeaderMap, Json(body): Json<Value>| async move {
+                let token = headers.get("x-token").and_then(|value| value.to_str().ok()).map(str::to_string);
+                received.lock().unwrap().push((token, body));
+            }))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let sink = SinkDef::Webhook {
+            url: format!("http://{}/hook", address),
+            headers: HashMap::from([("x-token".to_string(), "secret".to_string())]),
+        };
+        send(&sink, &alert()).await.unwrap();
+
+        let (token, body) = received.lock().unwrap().remove(0);
+        assert_eq!(token.as_deref(), Some("secret"));
+        assert_eq!(body["rule"], "BTC price above 100000 USD");
+        assert_eq!(body["text"], "BTC price is 110000.00 USD");
+
+        let missing = SinkDef::Webhook { url: format!("http://{}/missing", address), headers: HashMap::new() };
+        let error = send(&missing, &alert()).await.unwrap_err().to_string();
+        assert!(!error.contains(&address.to_string()), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_command_sink() {
+        let dir = tempdir().unwrap();
+        let out_file = dir.path().join("alert.txt");
+        // The message is the last argument, so sh gets it as $0
+        let sink = SinkDef::Command {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), format!("printf '%s|%s' \"$CRPT_ALERT_RULE\" \"$0\" > {}", out_file.display())],
+        };
+        send(&sink, &alert()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&out_file).unwrap(), "BTC price above 100000 USD|BTC price is 110000.00 USD");
+
+        let failing = SinkDef::Command { command: "false".to_string(), args: Vec::new() };
+        assert!(send(&failing, &alert()).await.is_err());
+    }
+
+    // Minimal SMTP server, returns the received mail data
+    async fn receive_mail(listener: tokio::net::TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+        let mut data = String::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let command = line.to_uppercase();
+            if command.starts_with("DATA") {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await.unwrap();
+                while let Some(line) = lines.next_line().await.unwrap() {
+                    if line == "." {
+                        break;
+                    }
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                writer.write_all(b"250 Ok\r\n").await.unwrap();
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                writer.write_all(b"250 Ok\r\n").await.unwrap();
+            }
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_email_sink() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(receive_mail(listener));
+
+        let sink = SinkDef::Email {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: SmtpSecurity::None,
+            username: None,
+            password_env: None,
+            from: "crpt@localhost".to_string(),
+            to: vec!["me@localhost".to_string()],
+        };
+        send(&sink, &alert()).await.unwrap();
+
+        let data = server.await.unwrap();
+        assert!(data.contains("Subject: Crypto alert: BTC price above 100000 USD"), "{}", data);
+        assert!(data.contains("To: me@localhost"), "{}", data);
+        assert!(data.contains("BTC price is 110000.00 USD"), "{}", data);
+    }
+}
@@ -0,0 +1,269 @@
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::{ Path, Query, Request, State };
+use axum::http::{ header::AUTHORIZATION, StatusCode };
+use axum::middleware::{ self, Next };
+use axum::response::{ IntoResponse, Response };
+use axum::routing::get;
+use axum::{ Json, Router };
+use serde::Deserialize;
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tokio::net::TcpListener;
+use tracing::{ debug, info, warn };
+
+use crate::coins::{ self, AthSnapshot, PricesSnapshot };
+use crate::config::coins::read_default_coins_config;
+use crate::config::paths::Paths;
+use crate::config::wallets::{ read_default_wallets_config, WalletsData };
+use crate::model::{ AthInfo, Currency, PriceInfo };
+use crate::service::{ allocation_summary, group_report_lines, holding_lines, HoldingsReport };
+
+mod openapi;
+mod query;
+
+use query::HoldingsQuery;
+
+// Token required in the Authorization: Bearer header, the API is open when it is not set
+pub const API_TOKEN_ENV: &str = "CRPT_API_TOKEN";
+
+struct ApiState {
+    paths: Paths,
+    token: Option<String>,
+}
+
+// Error returned as { "error": "..." }
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: String) -> Self {
+        ApiError { status: StatusCode::BAD_REQUEST, message }
+    }
+}
+
+// Configs and stored data which cannot be read
+impl From<crate::Error> for ApiError {
+    fn from(e: crate::Error) -> Self {
+        ApiError { status: StatusCode::INTERNAL_SERVER_ERROR, message: e.to_string() }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() {
+            warn!(error = %self.message, "Request failed");
+        }
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+type ApiResult<T> = core::result::Result<Json<T>, ApiError>;
+
+// Optional filters of the prices and all time data
+#[derive(Debug, Deserialize)]
+struct CoinQuery {
+    coin: Option<String>,
+    currency: Option<String>,
+}
+
+struct CoinFilter {
+    coin: Option<String>,
+    currency: Option<Currency>,
+}
+
+impl CoinQuery {
+    fn to_filter(&self) -> core::result::Result<CoinFilter, ApiError> {
+        Ok(CoinFilter { coin: self.coin.clone(), currency: parse_currency(self.currency.as_deref())? })
+    }
+}
+
+impl CoinFilter {
+    fn matches(&self, coin: &str, currency: &Currency) -> bool {
+        self.coin.as_ref().is_none_or(|filter_coin| filter_coin.eq_ignore_ascii_case(coin)) &&
+            self.currency.as_ref().is_none_or(|filter_currency| filter_currency == currency)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrencyQuery {
+    currency: Option<String>,
+}
+
+fn parse_currency(currency: Option<&str>) -> core::result::Result<Option<Currency>, ApiError> {
+    currency.map(|currency| currency.parse()).transpose().map_err(ApiError::bad_request)
+}
+
+// Serves the API until Ctrl-C. The data are read from the stored files at every request, prices are not fetched.
+pub async fn serve(paths: &Paths, bind: SocketAddr) -> crate::Result<()> {
+    let token = env::var(API_TOKEN_ENV).ok().filter(|token| !token.is_empty());
+    let listener = TcpListener::bind(bind).await.map_err(|e| format!("Cannot listen on {}: {}", bind, e))?;
+    let address = listener.local_addr()?;
+    if token.is_none() && !address.ip().is_loopback() {
+        warn!(%address, "API is reachable from the network without token, set {}", API_TOKEN_ENV);
+    }
+    info!(%address, auth = token.is_some(), "Serving API");
+
+    let app = router(ApiState { paths: paths.clone(), token });
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+            info!("Stopping API");
+        }).await?;
+    Ok(())
+}
+
+fn router(state: ApiState) -> Router {
+    let state = Arc::new(state);
+    let api = Router::new()
+        .route("/api/holdings", get(holdings))
+        .route("/api/wallets", get(wallets))
+        .route("/api/prices", get(prices))
+        .route("/api/prices/history", get(prices_history))
+        .route("/api/ath", get(ath))
+        .route("/api/ath/{coin}/history", get(ath_history))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
+
+    Router::new()
+        .route("/openapi.json", get(|| async { Json(openapi::openapi_spec()) }))
+        .merge(api)
+        .with_state(state)
+}
+
+async fn require_token(State(state): State<Arc<ApiState>>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| given.as_bytes().ct_eq(token.as_bytes()).into());
+        if !authorized {
+            return ApiError { status: StatusCode::UNAUTHORIZED, message: "Missing or invalid token".to_string() }.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn holdings(State(state): State<Arc<ApiState>>, Query(params): Query<Vec<(String, String)>>) -> ApiResult<HoldingsReport> {
+    let query = HoldingsQuery::parse(&params).map_err(ApiError::bad_request)?;
+    let paths = &state.paths;
+
+    let coins_data = read_default_coins_config(paths)?;
+    let wallets = read_default_wallets_config(paths)?.resolve_aliases(&coins_data);
+    let prices = coins::get_latest_prices(paths)?;
+    let ath_data = if query.ath { coins::get_ath_data(paths)? } else { Vec::new() };
+
+    debug!(filter = ?query.filter, "Getting report");
+    let lines = holding_lines(&wallets, &prices, &ath_data, &query.filter);
+    let summary = (query.summary || query.allocation_by.is_some())
+        .then(|| allocation_summary(&lines, &coins_data, query.top_n, query.allocation_by.as_ref()));
+    let report = HoldingsReport::new(group_report_lines(lines, &query.filter), query.filter.currency.clone());
+    Ok(Json(match summary {
+        Some(summary) => report.with_summary(summary),
+        None => report,
+    }))
+}
+
+async fn wallets(State(state): State<Arc<ApiState>>) -> ApiResult<WalletsData> {
+    let coins_data = read_default_coins_config(&state.paths)?;
+    Ok(Json(read_default_wallets_config(&state.paths)?.resolve_aliases(&coins_data)))
+}
+
+async fn prices(State(state): State<Arc<ApiState>>, Query(query): Query<CoinQuery>) -> ApiResult<Vec<PriceInfo>> {
+    let filter = query.to_filter()?;
+    let mut prices = coins::get_latest_prices(&state.paths)?;
+    prices.retain(|price| filter.matches(&price.coin, &price.currency));
+    Ok(Json(prices))
+}
+
+async fn prices_history(State(state): State<Arc<ApiState>>, Query(query): Query<CoinQuery>) -> ApiResult<Vec<PricesSnapshot>> {
+    let filter = query.to_filter()?;
+    let mut history = coins::get_prices_history(&state.paths)?;
+    for snapshot in history.iter_mut() {
+        snapshot.prices.retain(|price| filter.matches(&price.coin, &price.currency));
+    }
+    Ok(Json(history))
+}
+
+async fn ath(State(state): State<Arc<ApiState>>, Query(query): Query<CoinQuery>) -> ApiResult<Vec<AthInfo>> {
+    let filter = query.to_filter()?;
+    let mut ath_data = coins::get_ath_data(&state.paths)?;
+    ath_data.retain(|info| filter.matches(&info.coin, &info.currency));
+    Ok(Json(ath_data))
+}
+
+async fn ath_history(
+    State(state): State<Arc<ApiState>>,
+    Path(coin): Path<String>,
+    Query(query): Query<CurrencyQuery>
+) -> ApiResult<Vec<AthSnapshot>> {
+    let currency = parse_currency(query.currency.as_deref())?.unwrap_or_default();
+    Ok(Json(coins::get_ath_history(&state.paths, &coin.to_uppercase(), &currency)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{ to_bytes, Body };
+    use serde_json::Value;
+    use std::fs;
+    use tempfile::tempdir;
+    use tower::ServiceExt;
+
+    async fn get_json(app: &Router, uri: &str, token: Option<&str>) -> (StatusCode, Value) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_api() {
+        let dir = tempdir().unwrap();
+        let paths = Paths { config_dir: dir.path().join("conf"), data_dir: dir.path().join("data"), profile: None };
+        fs::create_dir_all(&paths.config_dir).unwrap();
+        fs::create_dir_all(&paths.data_dir).unwrap();
+        fs::write(paths.coins_config_file(), r#"{ "coins": [] }"#).unwrap();
+        fs::write(paths.wallets_config_file(), r#"{ "wallets": [
+            { "name": "Ledger", "kind": "Ledger", "address": "a", "holdings": [{ "coin": "BTC", "amount": 2.0 }, { "coin": "ETH", "amount": 1.0 }] },
+            { "name": "MetaMask", "kind": "MetaMask", "address": "b", "holdings": [{ "coin": "ETH", "amount": 3.0 }] }
+        ] }"#).unwrap();
+        fs::write(paths.data_dir.join("prices-202501101000.json"), r#"[
+            { "coin": "BTC", "currency": "USD", "value": 100000.0 },
+            { "coin": "ETH", "currency": "USD", "value": 2000.0 },
+            { "coin": "ETH", "currency": "EUR", "value": 1900.0 }
+        ]"#).unwrap();
+        let app = router(ApiState { paths, token: Some("secret".to_string()) });
+
+        let (status, _) = get_json(&app, "/api/prices", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = get_json(&app, "/api/prices", Some("wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, spec) = get_json(&app, "/openapi.json", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(spec["paths"]["/api/holdings"].is_object());
+
+        let (_, prices) = get_json(&app, "/api/prices?coin=eth&currency=EUR", Some("secret")).await;
+        assert_eq!(prices.as_array().unwrap().len(), 1);
+
+        let uri = "/api/holdings?token=ETH&wallet_name=*mask*&wallet_name=ledger&group_by=token";
+        let (status, report) = get_json(&app, uri, Some("secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["totals"]["lines"], 1);
+        assert_eq!(report["totals"]["value"], 8000.0);
+
+        let (status, error) = get_json(&app, "/api/holdings?sort_by=size", Some("secret")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error["error"].as_str().unwrap().starts_with("Invalid sort_by size"));
+    }
+}
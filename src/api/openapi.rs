@@ -0,0 +1,209 @@
+use serde_json::{ json, Value };
+
+// OpenAPI 3 description of the API, served at /openapi.json
+pub fn openapi_spec() -> Value {
+    let currency = json!({ "name": "currency", "in": "query", "schema": { "$ref": "#/components/schemas/Currency" } });
+    let coin = json!({ "name": "coin", "in": "query", "description": "Coin code, for example BTC", "schema": { "type": "string" } });
+    let list = |name: &str, description: &str| {
+        json!({ "name": name, "in": "query", "description": description, "schema": { "type": "string" } })
+    };
+    let error_responses = json!({
+        "400": { "description": "Invalid parameters", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "401": { "description": "Missing or invalid token", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "500": { "description": "Configs or data cannot be read", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } }
+    });
+    let ok = |schema: Value, description: &str| {
+        let mut responses = error_responses.clone();
+        responses["200"] = json!({ "description": description, "content": { "application/json": { "schema": schema } } });
+        responses
+    };
+    let array = |name: &str| json!({ "type": "array", "items": { "$ref": format!("#/components/schemas/{}", name) } });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "crpt-portfolio",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Holdings, wallets, prices and all time data from the stored data. Prices are not fetched, run update-prices or the daemon."
+        },
+        "security": [{ "bearer": [] }],
+        "paths": {
+            "/api/holdings": {
+                "get": {
+                    "summary": "Holdings report, the parameters are the options of the holdings command",
+                    "parameters": [
+                        list("token", "Tokens, repeated or comma separated"),
+                        list("exclude_token", "Tokens to leave out, repeated or comma separated"),
+                        list("wallet_name", "Wallet name glob or regex with re: prefix, repeated"),
+                        list("exclude_wallet", "Wallet names to leave out, repeated"),
+                        list("wallet_kind", "Wallet kinds, repeated or comma separated"),
+                        list("exclude_kind", "Wallet kinds to leave out, repeated or comma separated"),
+                        list("wallet_address", "Wallet addresses, repeated or comma separated"),
+                        { "name": "min_value", "in": "query", "schema": { "type": "number" } },
+                        { "name": "max_value", "in": "query", "schema": { "type": "number" } },
+                        currency,
+                        list("group_by", "Grouping levels - token, wallet, kind, tag, chain - comma separated"),
+                        { "name": "sort_by", "in": "query", "schema": { "type": "string", "enum": ["token", "amount", "value", "mkt-cap", "change24h", "from-ath"] } },
+                        { "name": "order", "in": "query", "schema": { "type": "string", "enum": ["asc", "desc"] } },
+                        { "name": "ath", "in": "query", "description": "Add all time data", "schema": { "type": "boolean" } },
+                        { "name": "summary", "in": "query", "description": "Add concentration summary", "schema": { "type": "boolean" } },
+                        { "name": "top_n", "in": "query", "schema": { "type": "integer", "default": 5 } },
+                        { "name": "allocation_by", "in": "query", "schema": { "type": "string", "enum": ["kind", "category"] } }
+                    ],
+                    "responses": ok(json!({ "$ref": "#/components/schemas/HoldingsReport" }), "Holdings report")
+                }
+            },
+            "/api/wallets": {
+                "get": {
+                    "summary": "Wallets with their holdings, aliases are resolved",
+                    "responses": ok(json!({ "$ref": "#/components/schemas/Wallets" }), "Wallets")
+                }
+            },
+            "/api/prices": {
+                "get": {
+                    "summary": "Latest stored prices",
+                    "parameters": [coin, currency],
+                    "responses": ok(array("PriceInfo"), "Prices")
+                }
+            },
+            "/api/prices/history": {
+                "get": {
+                    "summary": "All stored prices snapshots, from the oldest",
+                    "parameters": [coin, currency],
+                    "responses": ok(array("PricesSnapshot"), "Prices snapshots")
+                }
+            },
+            "/api/ath": {
+                "get": {
+                    "summary": "Latest stored all time data of the coins",
+                    "parameters": [coin, currency],
+                    "responses": ok(array("AthInfo"), "All time data")
+                }
+            },
+            "/api/ath/{coin}/history": {
+                "get": {
+                    "summary": "Stored all time data of the coin over time",
+                    "parameters": [
+                        { "name": "coin", "in": "path", "required": true, "schema": { "type": "string" } },
+                        currency
+                    ],
+                    "responses": ok(array("AthSnapshot"), "All time data snapshots")
+                }
+            }
+        },
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer", "description": "Required when CRPT_API_TOKEN is set" }
+            },
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": { "error": { "type": "string" } }
+                },
+                "Currency": { "type": "string", "enum": ["USD", "EUR"], "description": "Parameters are case-insensitive" },
+                "PriceInfo": {
+                    "type": "object",
+                    "properties": {
+                        "coin": { "type": "string" },
+                        "currency": { "$ref": "#/components/schemas/Currency" },
+                        "value": { "type": "number" },
+                        "market_cap": { "type": "number" },
+                        "change_24h": { "type": "number" }
+                    }
+                },
+                "PricesSnapshot": {
+                    "type": "object",
+                    "properties": {
+                        "timestamp": { "type": "string", "format": "date-time" },
+                        "prices": array("PriceInfo")
+                    }
+                },
+                "AthInfo": {
+                    "type": "object",
+                    "properties": {
+                        "coin": { "type": "string" },
+                        "currency": { "$ref": "#/components/schemas/Currency" },
+                        "ath": { "type": "number" },
+                        "ath_date": { "type": "string" },
+                        "ath_change_percentage": { "type": "number" },
+                        "atl": { "type": "number" },
+                        "atl_date": { "type": "string" },
+                        "atl_change_percentage": { "type": "number" }
+                    }
+                },
+                "AthSnapshot": {
+                    "type": "object",
+                    "properties": {
+                        "timestamp": { "type": "string", "format": "date-time" },
+                        "ath": { "$ref": "#/components/schemas/AthInfo" }
+                    }
+                },
+                "Wallets": {
+                    "type": "object",
+                    "properties": {
+                        "wallets": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string" },
+                                    "kind": { "type": "string" },
+                                    "address": { "type": "string" },
+                                    "tag": { "type": "string" },
+                                    "chain": { "type": "string" },
+                                    "holdings": {
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "properties": { "coin": { "type": "string" }, "amount": { "type": "number" } }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                "ReportLine": {
+                    "type": "object",
+                    "properties": {
+                        "token": { "type": "string" },
+                        "amount": { "type": "number" },
+                        "value": { "type": "number" },
+                        "allocation": { "type": "number" },
+                        "mkt_cap": { "type": "number" },
+                        "change_24h": { "type": "number" },
+                        "wallet_name": { "type": "string" },
+                        "wallet_kind": { "type": "string" },
+                        "wallet_address": { "type": "string" },
+                        "wallet_tag": { "type": "string", "nullable": true },
+                        "wallet_chain": { "type": "string", "nullable": true },
+                        "group": { "type": "array", "items": { "type": "string" } },
+                        "ath": { "type": "number" },
+                        "from_ath": { "type": "number" },
+                        "atl": { "type": "number" },
+                        "ath_date": { "type": "string" },
+                        "value_at_ath": { "type": "number" }
+                    }
+                },
+                "HoldingsReport": {
+                    "type": "object",
+                    "properties": {
+                        "currency": { "$ref": "#/components/schemas/Currency" },
+                        "lines": array("ReportLine"),
+                        "subtotals": array("ReportLine"),
+                        "totals": {
+                            "type": "object",
+                            "properties": {
+                                "lines": { "type": "integer" },
+                                "amount": { "type": "number" },
+                                "value": { "type": "number" },
+                                "value_at_ath": { "type": "number" }
+                            }
+                        },
+                        "summary": { "type": "object", "description": "Concentration summary, when requested" }
+                    }
+                }
+            }
+        }
+    })
+}
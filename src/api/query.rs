@@ -0,0 +1,85 @@
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::model::AllocationBy;
+use crate::service::ReportFilter;
+
+const DEFAULT_TOP_N: usize = 5;
+
+// Query of the holdings endpoint, the parameters have the names of the holdings command options
+#[derive(Debug)]
+pub struct HoldingsQuery {
+    pub filter: ReportFilter,
+    pub ath: bool,
+    pub summary: bool,
+    pub top_n: usize,
+    pub allocation_by: Option<AllocationBy>,
+}
+
+impl HoldingsQuery {
+    // Parameters can be repeated, the lists of tokens, kinds and addresses can be also comma separated.
+    // Wallet name patterns are not split, so a regex can contain commas.
+    pub fn parse(params: &[(String, String)]) -> Result<Self, String> {
+        let mut query = HoldingsQuery {
+            filter: ReportFilter::default(),
+            ath: false,
+            summary: false,
+            top_n: DEFAULT_TOP_N,
+            allocation_by: None,
+        };
+        let filter = &mut query.filter;
+        for (name, value) in params.iter() {
+            match name.as_str() {
+                "token" => filter.tokens.extend(split(value)),
+                "exclude_token" => filter.exclude_tokens.extend(split(value)),
+                "wallet_name" => filter.wallet_names.push(parse_value(name, value)?),
+                "exclude_wallet" => filter.exclude_wallet_names.push(parse_value(name, value)?),
+                "wallet_kind" => filter.wallet_kinds.extend(split(value)),
+                "exclude_kind" => filter.exclude_wallet_kinds.extend(split(value)),
+                "wallet_address" => filter.wallet_addresses.extend(split(value)),
+                "min_value" => filter.min_value = Some(parse_value(name, value)?),
+                "max_value" => filter.max_value = Some(parse_value(name, value)?),
+                "currency" => filter.currency = value.parse()?,
+                "group_by" => {
+                    for level in split(value) {
+                        filter.group_by.push(parse_enum(name, &level)?);
+                    }
+                }
+                "sort_by" => filter.sort_by = parse_enum(name, value)?,
+                "order" => filter.order = parse_enum(name, value)?,
+                "ath" => query.ath = parse_value(name, value)?,
+                "summary" => query.summary = parse_value(name, value)?,
+                "top_n" => query.top_n = parse_value(name, value)?,
+                "allocation_by" => query.allocation_by = Some(parse_enum(name, value)?),
+                _ => {
+                    return Err(format!("Unknown parameter {}", name));
+                }
+            }
+        }
+        Ok(query)
+    }
+}
+
+fn split(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+}
+
+fn parse_value<T>(name: &str, value: &str) -> Result<T, String> where T: FromStr, T::Err: ToString {
+    value.parse().map_err(|e: T::Err| format!("Invalid {} {}: {}", name, value, e.to_string()))
+}
+
+fn parse_enum<T: ValueEnum>(name: &str, value: &str) -> Result<T, String> {
+    T::from_str(value, true).map_err(|_| {
+        let values: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        format!("Invalid {} {}, use {}", name, value, values.join(", "))
+    })
+}
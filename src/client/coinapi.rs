@@ -1,4 +1,3 @@
-
 use reqwest::{header::{HeaderMap, HeaderValue, CONTENT_TYPE}, Client};
 use serde::Deserialize;
 use std::env;
@@ -6,18 +5,20 @@ use crate::Result;
 use crate::model::PriceInfo;
 use crate::model::Currency;
 
-use super::CurrentPriceApi;
+use super::{send_request, CurrentPriceApi};
 
-const API_KEY_ENV_PARAM: &str  = "COINAPI_API_KEY";
+pub const API_KEY_ENV_PARAM: &str  = "COINAPI_API_KEY";
 
 #[derive(Deserialize, Debug)]
 pub struct ExchangeRateResponse {
+    #[allow(unused)]
     pub asset_id_base: String,
     pub rates: Vec<Rate>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Rate {
+    #[allow(unused)]
     pub time: String,
     pub asset_id_quote: String,
     pub rate: f64,
@@ -39,21 +40,19 @@ impl CurrentPriceApi for CoinAPIClient {
 
         let rate_usd = response.rates
         .into_iter()
-        .filter(|rate| rate.asset_id_quote == "USD")
-        .next();
+        .find(|rate| rate.asset_id_quote == "USD");
 
         let Some(val) = rate_usd else {
             return Err(format!("Cannot find USD rate for: {}", symbol).into());
         };
         
-        let mut prices = Vec::new();
-        prices.push(PriceInfo { 
+        let prices = vec![PriceInfo { 
             coin: String::from(symbol),
             currency: Currency::USD,
             value: val.rate,
             market_cap: 0.0,
             change_24h: 0.0,
-        });
+        }];
 
         Ok(prices)
     }
@@ -62,7 +61,7 @@ impl CurrentPriceApi for CoinAPIClient {
 async fn get_coin_price(symbol :&str) -> Result<ExchangeRateResponse> {
     let url = format!("https://rest.coinapi.io/v1/exchangerate/{}", symbol);
 
-    let api_key = env::var(API_KEY_ENV_PARAM).expect(format!("{} not defined in env", API_KEY_ENV_PARAM).as_str());
+    let api_key = env::var(API_KEY_ENV_PARAM).map_err(|_| format!("{} not defined in env", API_KEY_ENV_PARAM))?;
 
     let mut headers = HeaderMap::new();
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
@@ -74,14 +73,14 @@ async fn get_coin_price(symbol :&str) -> Result<ExchangeRateResponse> {
 
     let client = Client::new();
 
-    let response = client
+    let request = client
         .get(url)
         .headers(headers)
-        .query(&query_params)
-        .send().await?
-        .json::<ExchangeRateResponse>().await?;
+        .query(&query_params);
+
+    let raw_text = send_request(&client, request).await?;
+    let response: ExchangeRateResponse = serde_json::from_str(&raw_text)?;
 
     Ok(response)
 
 }
-
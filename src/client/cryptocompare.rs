@@ -3,8 +3,9 @@ use serde::Deserialize;
 use std::{ collections::HashMap, env };
 use crate::Result;
 
+use super::send_request;
 
-const API_KEY_ENV_PARAM: &str = "CRYPTOCOMPARE_API_KEY";
+pub const API_KEY_ENV_PARAM: &str = "CRYPTOCOMPARE_API_KEY";
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "UPPERCASE")]
@@ -15,9 +16,11 @@ pub struct PriceMultiFullResponse {
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct Level2 {
+    #[allow(unused)]
     #[serde(rename = "FROMSYMBOL")]
     pub from_symbol: String,
     pub price: f64,
+    #[allow(unused)]
     #[serde(rename = "MKTCAP")]
     pub mktcap: f64,
     #[serde(rename = "CIRCULATINGSUPPLYMKTCAP")]
@@ -36,8 +39,8 @@ impl CryptoCompareClient {
 
     pub async fn get_coin_info(
         &self,
-        token_ids: &Vec<&str>,
-        currencies: &Vec<&str>
+        token_ids: &[&str],
+        currencies: &[&str]
     ) -> Result<PriceMultiFullResponse> {
         let base_url = "https://min-api.cryptocompare.com/data/pricemultifull";
         let ids = token_ids.join(","); // Join the token IDs with commas
@@ -45,7 +48,7 @@ impl CryptoCompareClient {
 
         let api_key = env
             ::var(API_KEY_ENV_PARAM)
-            .expect(format!("{} not defined in env", API_KEY_ENV_PARAM).as_str());
+            .map_err(|_| format!("{} not defined in env", API_KEY_ENV_PARAM))?;
 
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
@@ -58,8 +61,6 @@ impl CryptoCompareClient {
         let query_params_currency = vec![("tsyms", currencies)];
         let query_params_ids = vec![("fsyms", ids)];
 
-        //println!("{}", base_url);
-
         let client = Client::new();
 
         let request = client
@@ -68,17 +69,10 @@ impl CryptoCompareClient {
             .query(&query_params_currency)
             .query(&query_params_ids);
 
-        //println!("Request:{:?}", request);
-
-        let response = request.send().await?;
-
-        let raw_text = response.text().await?;
-        //println!("Raw response: {}", raw_text);
+        let raw_text = send_request(&client, request).await?;
         
         let result: PriceMultiFullResponse = serde_json::from_str(&raw_text)?;
 
-        //println!("{:#?}", result);
-
         Ok(result)
     }
 }
@@ -1,4 +1,12 @@
+use std::time::Instant;
+
+use reqwest::{ Client, Request, RequestBuilder, Url };
+use tracing::{ debug, trace };
+
+use crate::metrics::record_provider_request;
+
 use crate::model::PriceInfo;
+use crate::utils::redact::{ redact_headers, redact_url };
 use crate::Result;
 
 pub mod coingecko;
@@ -7,4 +15,42 @@ pub mod cryptocompare;
 
 pub trait CurrentPriceApi {
      async fn get_prices(&self, symbol: &str) -> Result<Vec<PriceInfo>>;
- }
\ No newline at end of file
+ }
+
+// Sends the request and returns the response body as text.
+// The request is logged with credentials in headers and query parameters redacted.
+pub async fn send_request(client: &Client, request: RequestBuilder) -> Result<String> {
+    let request = request.build()?;
+    let provider = provider_name(request.url());
+    let started = Instant::now();
+    let result = execute_request(client, request).await;
+    record_provider_request(&provider, result.is_ok(), started.elapsed());
+    result
+}
+
+// Provider of the request for the metrics, for example coingecko for api.coingecko.com
+fn provider_name(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    host.rsplit('.').nth(1).unwrap_or(host).to_string()
+}
+
+async fn execute_request(client: &Client, request: Request) -> Result<String> {
+    debug!(
+        method = %request.method(),
+        url = %redact_url(request.url()),
+        headers = ?redact_headers(request.headers()),
+        "Sending request"
+    );
+
+    let response = client.execute(request).await?;
+    let status = response.status();
+    let raw_text = response.text().await?;
+    debug!(%status, bytes = raw_text.len(), "Received response");
+    trace!(body = %raw_text, "Raw response");
+
+    if !status.is_success() {
+        return Err(format!("Request failed with status {}", status).into());
+    }
+
+    Ok(raw_text)
+}
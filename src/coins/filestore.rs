@@ -1,151 +1,333 @@
 use std::fs::{ self, File };
 use std::path;
 
-use chrono::Local;
-use reqwest::header::Entry;
-use serde::Serialize;
+use chrono::{ Local, NaiveDateTime };
+use serde::{ Deserialize, Serialize };
 use serde_json::to_writer_pretty;
+use tracing::{ debug, info };
 
 use crate::config::read_json_config;
-use crate::model::{AthInfo, PriceInfo};
+use crate::model::{AthInfo, Currency, PriceInfo};
 use crate::Result;
-use super::{CoinPriceStore, DataStore};
+use super::{AthSnapshot, CoinPriceStore, DataStore, PricesSnapshot};
+
+const PRICES_FILE_PREFIX: &str = "prices-";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d%H%M";
 
 pub struct CoinPriceFileStore {
-    pub dir_name: String,
+    pub dir_name: path::PathBuf,
 }
 
 impl CoinPriceStore for CoinPriceFileStore {
-    fn write_prices(&self, prices: &Vec<PriceInfo>) -> Result<String> {
+    fn write_prices(&self, prices: &[PriceInfo]) -> Result<String> {
         let now = Local::now(); // Get the current local date and time
-        let formatted_time = now.format("%Y%m%d%H%M"); // Format as "yyyymmddhhMM"
-        let filename = format!("prices-{}.json", formatted_time); // Construct the filename
-        let dir_and_filename = format!("{}/{}", &self.dir_name, filename);
+        let formatted_time = now.format(TIMESTAMP_FORMAT); // Format as "yyyymmddhhMM"
+        let filename = format!("{}{}.json", PRICES_FILE_PREFIX, formatted_time); // Construct the filename
+        let dir_and_filename = self.dir_name.join(&filename);
 
-        if !std::path::Path::new(&self.dir_name).exists() {
-            println!("Creating folder: {}", &self.dir_name);
-            fs::create_dir(&self.dir_name)?;
+        if !self.dir_name.exists() {
+            info!(dir = %self.dir_name.display(), "Creating folder");
+            fs::create_dir_all(&self.dir_name)?;
         }
 
         write_data_json_to_file(&dir_and_filename, &prices)?;
+        debug!(file = %dir_and_filename.display(), "Prices written");
 
         Ok(filename)
     }
 
     fn read_latest_prices(&self) -> Result<Option<Vec<PriceInfo>>> {
+        if !self.dir_name.exists() {
+            return Ok(None);
+        }
         if let Some(filename) = get_latest_prices_filename(&self.dir_name)? {
             return Ok(Some(read_json_config(&filename)?))
         }
         Ok(None)
     }
+
+    fn read_prices_history(&self) -> Result<Vec<PricesSnapshot>> {
+        if !self.dir_name.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut history = Vec::new();
+        for entry in fs::read_dir(&self.dir_name)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let Some(timestamp) = parse_prices_timestamp(&path) else {
+                continue;
+            };
+            history.push(PricesSnapshot {
+                timestamp,
+                prices: read_json_config(&path)?,
+            });
+        }
+
+        history.sort_by_key(|snapshot| snapshot.timestamp);
+        Ok(history)
+    }
+}
+
+// Returns the timestamp from the prices file name - prices-yyyymmddhhMM.json
+fn parse_prices_timestamp(path: &path::Path) -> Option<NaiveDateTime> {
+    let file_stem = path.file_stem()?.to_str()?;
+    let formatted_time = file_stem.strip_prefix(PRICES_FILE_PREFIX)?;
+    NaiveDateTime::parse_from_str(formatted_time, TIMESTAMP_FORMAT).ok()
 }
 
-pub fn write_data_json_to_file<T>(filename: &str, data: &T) -> Result<()>
+pub fn write_data_json_to_file<T>(filename: impl AsRef<path::Path>, data: &T) -> Result<()>
 where T: Serialize {
     let file = File::create(filename)?;
     to_writer_pretty(file, &data)?;
     Ok(())
 }
 
-fn get_latest_prices_filename(dir_name: &str) -> Result<Option<String>> {
+// Other files in the data dir, like the daemon status, are not prices snapshots
+fn get_latest_prices_filename(dir_name: &path::Path) -> Result<Option<path::PathBuf>> {
     let mut entries: Vec<_> = fs::read_dir(dir_name)?
         .filter_map(|entry| entry.ok())
-        .filter(|entry|
-            entry
-                .file_type()
-                .map(|t| t.is_file())
-                .unwrap_or(false)
-        )
-        .map(|entry| entry.file_name().to_string_lossy()
-        .into_owned())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && parse_prices_timestamp(path).is_some())
         .collect();
 
     entries.sort_by(|a,b| b.cmp(a));
 
-    if let Some(latest_file) = entries.first() {
-        let file_path = format!("{}/{}", dir_name, latest_file);
-        Ok(Some(file_path))
-    }
-    else {
-        Ok(None)
-    }
+    Ok(entries.into_iter().next())
 }
 
 
-const DIR_PREFIX_COIN_INFO: &str = "data/coins/";
-
+// Stores additional data of coins, one directory per coin and one file per update - <COIN>/yyyymmddhhMM.json.
+// The file contains the data in all updated currencies.
 pub struct AdditionalDataStore {
+    pub dir_name: path::PathBuf,
 }
 
 impl AdditionalDataStore {
+    // Returns all stored records of the coin in the currency, ordered from the oldest
+    pub fn read_data_history_for_coin(&self, coin: &str, currency: &Currency) -> Result<Vec<AthSnapshot>> {
+        let coin_dir = self.dir_name.join(coin);
+        if !coin_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut history = Vec::new();
+        for (timestamp, filename) in list_coin_data_files(&coin_dir)? {
+            let ath = read_coin_data_file(&filename)?
+                .into_iter()
+                .find(|ath_info| ath_info.currency == *currency);
+            if let Some(ath) = ath {
+                history.push(AthSnapshot { timestamp, ath });
+            }
+        }
+        Ok(history)
+    }
 }
 
 impl DataStore<Vec<AthInfo>> for AdditionalDataStore {
     fn write_data(&self, data: &Vec<AthInfo>) -> Result<Vec<String>> {
-        
         let now = Local::now(); // Get the current local date and time
-        let formatted_time = now.format("%Y%m%d%H%M"); // Format as "yyyymmddhhMM"
+        let formatted_time = now.format(TIMESTAMP_FORMAT); // Format as "yyyymmddhhMM"
 
-        let root_dir = path::Path::new(DIR_PREFIX_COIN_INFO);
+        let root_dir = self.dir_name.as_path();
         let mut filenames = Vec::new();
 
-        for coin_data in data {
-            let dirname = root_dir.join(&coin_data.coin);
-            if !std::path::Path::new(&dirname).exists() {
-                println!("Creating folder: {:?}", &dirname);
+        let mut coins: Vec<&str> = data.iter().map(|ath_info| ath_info.coin.as_str()).collect();
+        coins.sort();
+        coins.dedup();
+
+        for coin in coins {
+            let coin_data: Vec<&AthInfo> = data.iter().filter(|ath_info| ath_info.coin == coin).collect();
+            let dirname = root_dir.join(coin);
+            if !dirname.exists() {
+                debug!(dir = ?dirname, "Creating folder");
                 fs::create_dir_all(&dirname)?;
             }
-            else {
-                println!("Folder exists: {:?}", &dirname);
-            }
 
             let filename = format!("{}.json", formatted_time); // Construct the filename
-            println!("Writing to file: {:?}", filename);
             let dir_and_filename = dirname.join(filename);
             let dir_and_filename_str = dir_and_filename.to_str().ok_or("Failed to create filename")?;
-            write_data_json_to_file(&dir_and_filename_str, &coin_data)?;
+            debug!(file = %dir_and_filename_str, "Writing data");
+            write_data_json_to_file(dir_and_filename_str, &coin_data)?;
             filenames.push(dir_and_filename_str.to_string());
         }
 
         Ok(filenames)
     }
 
+    // Returns the latest records of every coin, in all stored currencies
     fn read_last_data_all(&self) -> Result<Option<Vec<AthInfo>>> {
-        let dir = path::Path::new(DIR_PREFIX_COIN_INFO);
+        let dir = self.dir_name.as_path();
         if !dir.exists() {
             return Ok(None);
         }
 
         let mut data = Vec::new();
         for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                
+            let coin_dir = entry?.path();
+            if !coin_dir.is_dir() {
+                continue;
             }
-
-            if path.is_file() {
-                let data_str = std::fs::read_to_string(&path)?;
-                let coin_data: AthInfo = serde_json::from_str(&data_str)?;
-                data.push(coin_data);
+            if let Some((_, filename)) = list_coin_data_files(&coin_dir)?.pop() {
+                data.append(&mut read_coin_data_file(&filename)?);
             }
         }
+        data.sort_by(|a, b| a.coin.cmp(&b.coin));
 
         Ok(Some(data))
     }
 
     fn read_last_data_for_coin(&self, coin: &str) -> Result<Option<Vec<AthInfo>>> {
-        let Ok(dirname)  = crate::utils::file_utils::find_subdir_with_name(path::Path::new(DIR_PREFIX_COIN_INFO), coin) else {
+        let dir = self.dir_name.as_path();
+        if !dir.exists() {
             return Ok(None);
-        };
-        let Some(dirname) = dirname else {
+        }
+        let Some(dirname) = crate::utils::file_utils::find_subdir_with_name(dir, coin)? else {
             return Ok(None);
         };
-        let Some(filename) = crate::utils::file_utils::get_latest_filename(&dirname)? else {
+        let Some((_, filename)) = list_coin_data_files(path::Path::new(&dirname))?.pop() else {
             return Ok(None);
         };
-        Ok(Some(read_json_config(filename.to_str().unwrap())?))
+        Ok(Some(read_coin_data_file(&filename)?))
+    }
+}
+
+// Returns the data files of the coin with their timestamps, ordered from the oldest.
+// Files without a timestamp in the name are skipped.
+fn list_coin_data_files(coin_dir: &path::Path) -> Result<Vec<(NaiveDateTime, path::PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(coin_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Some(timestamp) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| NaiveDateTime::parse_from_str(stem, TIMESTAMP_FORMAT).ok()) else {
+            continue;
+        };
+        files.push((timestamp, path));
+    }
+    files.sort_by_key(|(timestamp, _)| *timestamp);
+    Ok(files)
+}
+
+// Older files contain only one record in USD, newer contain records for all currencies
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CoinDataFile {
+    Records(Vec<AthInfo>),
+    Single(AthInfo),
+}
+
+fn read_coin_data_file(filename: &path::Path) -> Result<Vec<AthInfo>> {
+    let filename = filename.to_str().ok_or("Invalid file name")?;
+    match read_json_config(filename)? {
+        CoinDataFile::Records(records) => Ok(records),
+        CoinDataFile::Single(record) => Ok(vec![record]),
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
 
+    fn ath_info(coin: &str, currency: Currency, ath: f64) -> AthInfo {
+        AthInfo {
+            coin: coin.to_string(),
+            currency,
+            ath,
+            ath_date: "2021-11-10T14:24:11.849Z".to_string(),
+            ath_change_percentage: 0.0,
+            atl: 1.0,
+            atl_date: "2013-07-06T00:00:00.000Z".to_string(),
+            atl_change_percentage: 0.0,
+        }
+    }
+
+    fn write_coin_file(dir: &path::Path, coin: &str, timestamp: &str, ath: f64) {
+        let coin_dir = dir.join(coin);
+        fs::create_dir_all(&coin_dir).unwrap();
+        let filename = coin_dir.join(format!("{}.json", timestamp));
+        let records = vec![ath_info(coin, Currency::USD, ath), ath_info(coin, Currency::EUR, ath * 0.9)];
+        write_data_json_to_file(filename.to_str().unwrap(), &records).unwrap();
+    }
+
+    #[test]
+    fn test_read_last_data() {
+        let dir = tempdir().unwrap();
+        write_coin_file(dir.path(), "BTC", "202411010000", 69000.0);
+        write_coin_file(dir.path(), "BTC", "202412010000", 100000.0);
+        write_coin_file(dir.path(), "ETH", "202412010000", 4800.0);
+        fs::write(dir.path().join("BTC").join("notes.txt"), "not data").unwrap();
+        fs::write(dir.path().join("README"), "not a coin").unwrap();
+        fs::create_dir_all(dir.path().join("EMPTY")).unwrap();
+
+        let store = AdditionalDataStore {
+            dir_name: dir.path().to_path_buf(),
+        };
+
+        let all = store.read_last_data_all().unwrap().unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].coin, "BTC");
+        assert_eq!(all[0].ath, 100000.0);
+        assert_eq!(all[1].currency, Currency::EUR);
+        assert_eq!(all[2].ath, 4800.0);
+
+        let btc = store.read_last_data_for_coin("BTC").unwrap().unwrap();
+        assert_eq!(btc[0].ath, 100000.0);
+        assert!(store.read_last_data_for_coin("DOT").unwrap().is_none());
+
+        let history = store.read_data_history_for_coin("BTC", &Currency::EUR).unwrap();
+        let aths: Vec<f64> = history.iter().map(|snapshot| snapshot.ath.ath).collect();
+        assert_eq!(aths, vec![62100.0, 90000.0]);
+        assert!(store.read_data_history_for_coin("DOT", &Currency::USD).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_legacy_data() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("BTC")).unwrap();
+        let legacy = serde_json::json!({
+            "coin": "BTC",
+            "ath": 69000.0,
+            "ath_date": "2021-11-10T14:24:11.849Z",
+            "ath_change_percentage": 0.0,
+            "atl": 67.81,
+            "atl_date": "2013-07-06T00:00:00.000Z",
+            "atl_change_percentage": 0.0
+        });
+        fs::write(dir.path().join("BTC").join("202401010000.json"), legacy.to_string()).unwrap();
+
+        let store = AdditionalDataStore {
+            dir_name: dir.path().to_path_buf(),
+        };
+        let all = store.read_last_data_all().unwrap().unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].currency, Currency::USD);
+    }
+
+    #[test]
+    fn test_write_and_read_data() {
+        let dir = tempdir().unwrap();
+        let store = AdditionalDataStore {
+            dir_name: dir.path().join("coins"),
+        };
+        assert!(store.read_last_data_all().unwrap().is_none());
+
+        let data = vec![
+            ath_info("BTC", Currency::USD, 1.0),
+            ath_info("BTC", Currency::EUR, 0.9),
+            ath_info("ETH", Currency::USD, 2.0)
+        ];
+        let filenames = store.write_data(&data).unwrap();
+        assert_eq!(filenames.len(), 2);
+
+        let all = store.read_last_data_all().unwrap().unwrap();
+        assert_eq!(all.iter().map(|info| info.coin.as_str()).collect::<Vec<_>>(), vec!["BTC", "BTC", "ETH"]);
+    }
+}
@@ -1,35 +1,35 @@
-use chrono::Local;
-use filestore::CoinPriceFileStore;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use filestore::{ AdditionalDataStore, CoinPriceFileStore };
+use tracing::{ debug, info, warn };
 
-use crate::model::{AthInfo, PriceInfo};
+use crate::model::{ AthInfo, Currency, PriceInfo };
 use crate::provider::get_price_provider;
 use crate::service::ReportFilter;
 use crate::Result;
-use crate::config::coins::{ read_default_coins_config, CoinsData };
+use crate::config::coins::CoinsData;
+use crate::config::paths::Paths;
 
 pub(crate) mod filestore;
 pub mod update_prices;
 pub(crate) mod update_ath;
 
-use std::fs::{ File };
-
 //:TODO - asi by sme mali ignorovat filter, lebo potom sa nevratia vsetky coins
-pub async fn get_coins_prices(filter: &ReportFilter) -> Result<Vec<PriceInfo>> {
+// Returns the latest stored prices, the prices of the coins are fetched and stored when no prices are stored
+pub async fn get_coins_prices(paths: &Paths, coins_data: &CoinsData, filter: &ReportFilter) -> Result<Vec<PriceInfo>> {
 
     let coin_price_store = CoinPriceFileStore {
-        dir_name: String::from("data"),
+        dir_name: paths.prices_dir(),
     };
     if let Some(latest_prices) = coin_price_store.read_latest_prices()? {
-        println!("Found latest prices");
+        debug!("Found latest prices");
         return Ok(latest_prices);
     }
 
     //TODO -check if some coins are not missing
-    println!("Getting coins config");
-    let coins_data: CoinsData = read_default_coins_config()?;
-    let price_info = get_coins_prices_for_coins_data(&filter, &coins_data).await?;
+    let price_info = get_coins_prices_for_coins_data(filter, coins_data).await?;
 
-    println!("Storing prices");
+    info!(prices = price_info.len(), "Storing prices");
     coin_price_store.write_prices(&price_info)?;
 
     Ok(price_info)
@@ -37,16 +37,45 @@ pub async fn get_coins_prices(filter: &ReportFilter) -> Result<Vec<PriceInfo>> {
 }
 
 
+// Returns the latest stored prices, empty when no prices are stored
+pub fn get_latest_prices(paths: &Paths) -> Result<Vec<PriceInfo>> {
+    let coin_price_store = CoinPriceFileStore {
+        dir_name: paths.prices_dir(),
+    };
+    Ok(coin_price_store.read_latest_prices()?.unwrap_or_default())
+}
+
+// Returns all stored prices snapshots, ordered from the oldest
+pub fn get_prices_history(paths: &Paths) -> Result<Vec<PricesSnapshot>> {
+    let coin_price_store = CoinPriceFileStore {
+        dir_name: paths.prices_dir(),
+    };
+    coin_price_store.read_prices_history()
+}
+
+// Returns the latest stored all time data of all coins, in all stored currencies
+pub fn get_ath_data(paths: &Paths) -> Result<Vec<AthInfo>> {
+    let ath_store = AdditionalDataStore {
+        dir_name: paths.coin_info_dir(),
+    };
+    Ok(ath_store.read_last_data_all()?.unwrap_or_default())
+}
+
+// Returns all stored all time data of the coin in the currency, ordered from the oldest
+pub fn get_ath_history(paths: &Paths, coin: &str, currency: &Currency) -> Result<Vec<AthSnapshot>> {
+    let ath_store = AdditionalDataStore {
+        dir_name: paths.coin_info_dir(),
+    };
+    ath_store.read_data_history_for_coin(coin, currency)
+}
+
 async fn get_coins_prices_for_coins_data(filter: &ReportFilter, coins_data: &CoinsData) -> Result<Vec<PriceInfo>> {
     let mut result_prices: Vec<PriceInfo> = Vec::new();
     for coin_def in coins_data.coins.iter() {
-        if let Some(coin_filter) = filter.coin.as_ref() {
-            if *coin_filter != coin_def.code {
-                continue;
-            }
+        if !filter.matches_token(&coin_def.code) {
+            continue;
         }
 
-        //println!("getting info for coin: {}", coin_def.code);
         let price_provider = get_price_provider(&coin_def.price_provider);
         let coin_result = price_provider.get_price(
             &coin_def.code,
@@ -54,27 +83,44 @@ async fn get_coins_prices_for_coins_data(filter: &ReportFilter, coins_data: &Coi
             &Vec::new()
         ).await;
 
-        if coin_result.is_err() {
-            println!("Error reading coin: {} skipping", coin_def.code);
-            continue;
-        }
+        let mut coin_result = match coin_result {
+            Ok(coin_result) => coin_result,
+            Err(e) => {
+                warn!(coin = %coin_def.code, error = %e, "Error reading coin, skipping");
+                continue;
+            }
+        };
 
-        let mut coin_result = coin_result.unwrap();
-        println!("Price: {}:{:?}", coin_def.code, coin_result);
+        debug!(coin = %coin_def.code, prices = ?coin_result, "Price");
         result_prices.append(&mut coin_result);
     }
 
     Ok(result_prices)
 }
 
+// Prices as they were stored at the time
+#[derive(Serialize)]
+pub struct PricesSnapshot {
+    pub timestamp: NaiveDateTime,
+    pub prices: Vec<PriceInfo>,
+}
+
+// All time data of a coin as they were stored at the time
+#[derive(Serialize)]
+pub struct AthSnapshot {
+    pub timestamp: NaiveDateTime,
+    pub ath: AthInfo,
+}
+
 trait CoinPriceStore {
-    fn write_prices(&self, prices: &Vec<PriceInfo>) -> Result<String>;
+    fn write_prices(&self, prices: &[PriceInfo]) -> Result<String>;
     fn read_latest_prices(&self) -> Result<Option<Vec<PriceInfo>>>;
+    fn read_prices_history(&self) -> Result<Vec<PricesSnapshot>>;
 }
 
+#[allow(unused)]
 trait DataStore<T> {
     fn write_data(&self, data: &T ) -> Result<Vec<String>>;
     fn read_last_data_for_coin(&self, coin: &str) -> Result<Option<T>>;
     fn read_last_data_all(&self) -> Result<Option<T>>;
 }
-
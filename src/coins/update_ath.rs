@@ -1,66 +1,140 @@
+use std::{collections::HashMap, fs, path::Path, time::Duration};
 
+use serde::{ Deserialize, Serialize };
+use tracing::{ debug, info };
 
-use std::{io::{self, Write}, thread, time::Duration};
-
-use crate::{coins::{filestore::AdditionalDataStore, DataStore}, config::coins::{read_default_coins_config, CoinsData}, model::{AthInfo, Currency}, provider::get_ath_provider};
+use crate::{
+    client::coingecko::MARKETS_PAGE_SIZE,
+    coins::{filestore::{write_data_json_to_file, AdditionalDataStore}, DataStore},
+    config::{coins::{read_default_coins_config, AthProviderEnum, CoinDef, CoinsData}, paths::Paths, read_json_config},
+    model::Currency,
+    provider::get_ath_provider,
+};
 use crate::Result;
 
-pub async fn update_ath_data_for_token(token: &str, currencies: &Vec<Currency>) -> Result<()> {
-    println!("Updating ATH data for token: {}", token);
+// Coins in one batch, a full page of the CoinGecko markets endpoint. The progress is saved after each page,
+// so a failed update resumes from the page which failed.
+const ATH_BATCH_SIZE: usize = MARKETS_PAGE_SIZE;
+
+const BATCH_DELAY_SECONDS: u64 = 20;
+
+pub async fn update_ath_data_for_token(paths: &Paths, token: &str, currencies: &[Currency]) -> Result<()> {
+    info!(token, "Updating ATH data for token");
     let data_store = AdditionalDataStore {
+        dir_name: paths.coin_info_dir(),
     };
     
-    println!("Getting coins config");
+    debug!("Getting coins config");
 
-    let coins_data: CoinsData = read_default_coins_config()?;
+    let coins_data: CoinsData = read_default_coins_config(paths)?;
     let Some(coin_def) = coins_data.get_coin_def(token) else {
         return Err(format!("Coin {} not found", token).into());
     };
     
     let ath_provider = get_ath_provider(&coin_def.ath_provider);
-    println!("Getting ATH for: {}", token);
+    debug!(token, "Getting ATH");
 
     let ath_info = ath_provider.get_ath(&coin_def.code, coin_def.ath_provider_data.as_ref(), currencies).await?;
-    println!("ATH info: {:?}", ath_info);
+    debug!(?ath_info, "ATH info");
 
     data_store.write_data(&ath_info)?;
 
     Ok(())
 }
 
-pub async fn update_ath_data_for_all_tokens(currencies: &Vec<Currency>) -> Result<()> {
-    println!("Updating ATH data for all tokens");
+// Updates the data of all coins in batches, a batch is one markets request per currency.
+// Updated coins are stored in the progress file after each batch, so an interrupted update
+// continues with the remaining coins. The progress file is removed when all coins are updated.
+pub async fn update_ath_data_for_all_tokens(paths: &Paths, currencies: &[Currency], restart: bool) -> Result<()> {
+    info!("Updating ATH data for all tokens");
     let data_store = AdditionalDataStore {
+        dir_name: paths.coin_info_dir(),
     };
+    let progress_file = paths.ath_progress_file();
 
-    println!("Getting coins config");
+    debug!("Getting coins config");
+    let coins_data: CoinsData = read_default_coins_config(paths)?;
 
-    let coins_data: CoinsData = read_default_coins_config()?;
+    let mut progress = if restart { AthUpdateProgress::default() } else { read_progress(&progress_file)? };
+    if !progress.updated.is_empty() {
+        info!(updated = progress.updated.len(), "Resuming ATH update");
+    }
+
+    let mut coins_by_provider: HashMap<&AthProviderEnum, Vec<CoinDef>> = HashMap::new();
     for coin_def in coins_data.coins.iter() {
-        let ath_provider = get_ath_provider(&coin_def.ath_provider);
-        println!("Getting ATH for: {}", coin_def.code);
-
-        let res = ath_provider.get_ath(&coin_def.code, coin_def.ath_provider_data.as_ref(), currencies).await;
-        
-        match res {
-            Err(e) => {
-                println!("Error getting ATH for: {} - {}", coin_def.code, e);
-            },
-            Ok(ath_info) => {
-                println!("ATH info: {:?}", ath_info);
-                data_store.write_data(&ath_info)?;
-            }
+        if !progress.updated.contains(&coin_def.code) {
+            coins_by_provider.entry(&coin_def.ath_provider).or_default().push(coin_def.clone());
         }
+    }
 
-        let mut sleep = 20;
-        println!("Sleeping for {sleep} seconds");
-        while sleep > 0 {
-            print!(".");
-            io::stdout().flush().unwrap();
-            thread::sleep(Duration::from_secs(1));
-            sleep -= 1;
+    let mut first_batch = true;
+    for (provider_id, coins) in coins_by_provider.iter() {
+        let ath_provider = get_ath_provider(provider_id);
+        for batch in coins.chunks(ATH_BATCH_SIZE) {
+            if !first_batch {
+                wait_between_batches().await;
+            }
+            first_batch = false;
+
+            info!(provider = ?provider_id, coins = batch.len(), "Getting ATH");
+            let ath_info = ath_provider.get_aths(batch, currencies).await?;
+            debug!(records = ath_info.len(), "ATH info");
+            data_store.write_data(&ath_info)?;
+
+            progress.updated.extend(batch.iter().map(|coin_def| coin_def.code.clone()));
+            write_progress(&progress_file, &progress)?;
         }
     }
 
+    if progress_file.exists() {
+        fs::remove_file(&progress_file)?;
+    }
+    info!(updated = progress.updated.len(), "ATH data updated");
+
     Ok(())
-}
\ No newline at end of file
+}
+
+// Coins of which the data were already stored during the interrupted update
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AthUpdateProgress {
+    updated: Vec<String>,
+}
+
+fn read_progress(progress_file: &Path) -> Result<AthUpdateProgress> {
+    if !progress_file.exists() {
+        return Ok(AthUpdateProgress::default());
+    }
+    read_json_config(progress_file)
+}
+
+fn write_progress(progress_file: &Path, progress: &AthUpdateProgress) -> Result<()> {
+    if let Some(dir) = progress_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_data_json_to_file(progress_file, progress)
+}
+
+// Waits asynchronously, so the shutdown signal of the daemon is handled during the wait
+async fn wait_between_batches() {
+    debug!(seconds = BATCH_DELAY_SECONDS, "Waiting before the next batch");
+    tokio::time::sleep(Duration::from_secs(BATCH_DELAY_SECONDS)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_progress_file() {
+        let dir = tempdir().unwrap();
+        let progress_file = dir.path().join("data").join("progress.json");
+        assert!(read_progress(&progress_file).unwrap().updated.is_empty());
+
+        let progress = AthUpdateProgress {
+            updated: vec!["BTC".to_string(), "ETH".to_string()],
+        };
+        write_progress(&progress_file, &progress).unwrap();
+        assert_eq!(read_progress(&progress_file).unwrap().updated, vec!["BTC", "ETH"]);
+    }
+}
@@ -1,46 +1,90 @@
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
+
+use tracing::{ debug, info };
 
 use crate::coins::CoinPriceStore;
 use crate::config::coins::{read_default_coins_config, CoinDef, CoinsData, PriceProviderEnum};
+use crate::config::paths::Paths;
 use crate::model::{Currency, PriceInfo};
 use crate::provider::get_price_provider;
 use crate::Result;
 use crate::coins::filestore::CoinPriceFileStore;
 
-pub async fn update_coins_prices(currencies: &Vec<Currency>) -> Result<()> {
+pub async fn update_coins_prices(paths: &Paths, currencies: &[Currency]) -> Result<()> {
+    debug!("Getting coins config");
+
+    let coins_data: CoinsData = read_default_coins_config(paths)?;
+    update_prices_for_coins(paths, &coins_data, currencies).await
+}
+
+// Updates the prices of the coins, for example of the coins of more profiles merged together
+pub async fn update_prices_for_coins(paths: &Paths, coins_data: &CoinsData, currencies: &[Currency]) -> Result<()> {
     let coin_price_store = CoinPriceFileStore {
-        dir_name: String::from("data"),
+        dir_name: paths.prices_dir(),
     };
-    
-    println!("Getting coins config");
 
-    let coins_data: CoinsData = read_default_coins_config()?;
-    let price_info = get_coins_prices_for_coins_data(&coins_data, currencies).await?;
+    let mut price_info = get_coins_prices_for_coins_data(coins_data, currencies).await?;
 
-    println!("Storing prices: {}", price_info.len());
+    if let Some(latest_prices) = coin_price_store.read_latest_prices()? {
+        keep_other_prices(&mut price_info, latest_prices, coins_data);
+    }
+
+    info!(prices = price_info.len(), "Storing prices");
     coin_price_store.write_prices(&price_info)?;
     Ok(())
 }
 
-async fn get_coins_prices_for_coins_data(coins_data: &CoinsData, currencies: &Vec<Currency>) -> Result<Vec<PriceInfo>> {
+// Prices are shared by the profiles, so the prices of coins from other profiles are kept
+fn keep_other_prices(price_info: &mut Vec<PriceInfo>, latest_prices: Vec<PriceInfo>, coins_data: &CoinsData) {
+    let kept: Vec<PriceInfo> = latest_prices
+        .into_iter()
+        .filter(|price| coins_data.get_coin_def(&price.coin).is_none())
+        .collect();
+    debug!(kept = kept.len(), "Keeping prices of other coins");
+    price_info.extend(kept);
+}
+
+async fn get_coins_prices_for_coins_data(coins_data: &CoinsData, currencies: &[Currency]) -> Result<Vec<PriceInfo>> {
     let mut result_prices: Vec<PriceInfo> = Vec::new();
 
+    // Merged profiles can define the same coin, its price is requested once
     let mut providers: HashMap<&PriceProviderEnum, Vec<CoinDef>> = HashMap::new();
-    for coin_def in coins_data.coins.iter() {
+    let mut requested: HashSet<&str> = HashSet::new();
+    for coin_def in coins_data.coins.iter().filter(|coin_def| requested.insert(&coin_def.code)) {
         providers.entry(&coin_def.price_provider)
-        .or_insert_with(Vec::new)
+        .or_default()
         .push(coin_def.clone());
     }
 
-    println!("Providers: {:?}", providers.keys());
+    debug!(providers = ?providers.keys(), "Providers");
     for (provider, coins) in providers {
-        println!("Processing provider:{:?}", provider);
-        let provider_impl = get_price_provider(&provider);
-        let mut prices = provider_impl.get_prices(&coins, &currencies).await?;
+        info!(?provider, coins = coins.len(), "Processing provider");
+        let provider_impl = get_price_provider(provider);
+        let mut prices = provider_impl.get_prices(&coins, currencies).await?;
         result_prices.append(&mut prices);
     }
 
-    //println!("Result prices: {:#?}", result_prices);
-
     Ok(result_prices)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(coin: &str, value: f64) -> PriceInfo {
+        PriceInfo { coin: coin.to_string(), currency: Currency::USD, value, market_cap: 0.0, change_24h: 0.0 }
+    }
+
+    #[test]
+    fn test_keep_other_prices() {
+        let coins_data: CoinsData = serde_json::from_value(serde_json::json!({
+            "coins": [{ "code": "BTC" }, { "code": "ETH" }]
+        })).unwrap();
+        // ETH failed to fetch, its old price is not kept as it belongs to this profile
+        let mut prices = vec![price("BTC", 100000.0)];
+        keep_other_prices(&mut prices, vec![price("BTC", 90000.0), price("ETH", 2000.0), price("SOL", 150.0)], &coins_data);
+
+        let prices: Vec<(&str, f64)> = prices.iter().map(|price| (price.coin.as_str(), price.value)).collect();
+        assert_eq!(prices, vec![("BTC", 100000.0), ("SOL", 150.0)]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use serde::{ Deserialize, Serialize };
+
+use crate::model::Currency;
+use crate::Result;
+
+use super::paths::Paths;
+use super::{ read_config, unversioned, Migration };
+
+// Migrations of older versions of the alerts config
+pub const ALERTS_MIGRATIONS: &[Migration] = &[unversioned];
+
+// Alert rules and the sinks which are notified when a rule is triggered
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
+    #[serde(default)]
+    pub sinks: Vec<SinkDef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    // Name of the rule in the notifications, the description of the condition is used when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub currency: Currency,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum AlertCondition {
+    PriceAbove { coin: String, value: f64 },
+    PriceBelow { coin: String, value: f64 },
+    // Price changed in 24 hours by more than the percent, up or down
+    Change24h { coin: String, percent: f64 },
+    // Value of all holdings in the wallets
+    PortfolioAbove { value: f64 },
+    PortfolioBelow { value: f64 },
+    // Price is more than the percent below the all time high
+    FromAth { coin: String, percent: f64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SinkDef {
+    Stdout,
+    // Runs the command with the alert message as the last argument, for example notify-send
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    // Posts the alert as json
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    Email {
+        host: String,
+        // Default port of the security - 25, 587 for STARTTLS and 465 for TLS
+        #[serde(default)]
+        port: Option<u16>,
+        #[serde(default)]
+        security: SmtpSecurity,
+        #[serde(default)]
+        username: Option<String>,
+        // Env variable with the password, so the password is not stored in the config
+        #[serde(default)]
+        password_env: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SmtpSecurity {
+    // Plain connection, for local mail servers
+    None,
+    #[default]
+    StartTls,
+    Tls,
+}
+
+// Returns the empty config when the alerts file does not exist
+pub fn read_default_alerts_config(paths: &Paths) -> Result<AlertsConfig> {
+    let file_path = paths.alerts_config_file();
+    if !file_path.exists() {
+        return Ok(AlertsConfig::default());
+    }
+    read_config(file_path, ALERTS_MIGRATIONS)
+}
@@ -0,0 +1,72 @@
+use serde::{ Deserialize, Serialize };
+
+use crate::model::Currency;
+use crate::Result;
+
+use super::paths::Paths;
+use super::{ read_config, unversioned, Migration };
+
+// Migrations of older versions of the app config
+pub const APP_MIGRATIONS: &[Migration] = &[unversioned];
+
+// General settings of the application
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppConfig {
+    // Currencies in which prices and all time data are updated
+    #[serde(default = "default_currencies")]
+    pub currencies: Vec<Currency>,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+}
+
+// Schedules of the daemon jobs, cron expressions in local time. The seconds field is optional.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonConfig {
+    #[serde(default = "default_prices_schedule")]
+    pub prices_schedule: String,
+    #[serde(default = "default_ath_schedule")]
+    pub ath_schedule: String,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            prices_schedule: default_prices_schedule(),
+            ath_schedule: default_ath_schedule(),
+        }
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            currencies: default_currencies(),
+            daemon: DaemonConfig::default(),
+        }
+    }
+}
+
+fn default_currencies() -> Vec<Currency> {
+    vec![Currency::USD, Currency::EUR]
+}
+
+// Every 15 minutes
+fn default_prices_schedule() -> String {
+    "*/15 * * * *".to_string()
+}
+
+// Every day at 3:00, the update of all tokens takes long because of the rate limits
+fn default_ath_schedule() -> String {
+    "0 3 * * *".to_string()
+}
+
+// Returns the default settings when the config file does not exist
+pub fn read_default_app_config(paths: &Paths) -> Result<AppConfig> {
+    let file_path = paths.app_config_file();
+    if !file_path.exists() {
+        return Ok(AppConfig::default());
+    }
+    read_config(file_path, APP_MIGRATIONS)
+}
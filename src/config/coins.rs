@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use crate::{client::coingecko::Coin, Result};
+use crate::Result;
 
-use super::read_json_config;
+use super::paths::Paths;
+use super::{ read_config, unversioned, write_config, Migration };
+
+// Migrations of older versions of the coins config
+pub const COINS_MIGRATIONS: &[Migration] = &[unversioned];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CoinsData {
@@ -13,6 +17,15 @@ pub struct CoinsData {
 #[serde(rename_all = "camelCase")]
 pub struct CoinDef {
     pub code: String,
+    // Full name of the coin, a hint for resolving the provider ids of coins with the same code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Custom categories of the token, for example L1, DeFi, stablecoin
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    // Other codes of the coin, for example old ticker after rename or redenomination
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<CoinAlias>,
     #[serde(default)]
     pub price_provider: PriceProviderEnum,
     #[serde(default)]
@@ -23,39 +36,124 @@ pub struct CoinDef {
     pub ath_provider_data: Option<HashMap<String, String>>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Hash, Clone)]
+// Holdings in the alias code are converted into the coin, amount is multiplied by the ratio.
+// For example 1000 LEND were swapped for 10 AAVE, so LEND is an alias of AAVE with ratio 0.01
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoinAlias {
+    pub code: String,
+    #[serde(default = "default_alias_ratio")]
+    pub ratio: f64,
+}
+
+fn default_alias_ratio() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Hash, Clone, Default)]
 #[serde(rename_all = "PascalCase")]
 pub enum PriceProviderEnum {
     CoinAPI,
     CoinGecko,
+    #[default]
     CryptoCompare,
     // Add more providers here if needed
 }
 
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Hash, Clone, Default)]
 #[serde(rename_all = "PascalCase")]
 pub enum AthProviderEnum {
+    #[default]
     CoinGecko
 }
 
-impl Default for PriceProviderEnum {
-    fn default() -> Self {
-        PriceProviderEnum::CryptoCompare
-    }
+impl PriceProviderEnum {
+    pub const ALL: [PriceProviderEnum; 3] = [PriceProviderEnum::CoinAPI, PriceProviderEnum::CoinGecko, PriceProviderEnum::CryptoCompare];
 }
 
-impl Default for AthProviderEnum {
-    fn default() -> Self {
-        AthProviderEnum::CoinGecko
+impl AthProviderEnum {
+    pub const ALL: [AthProviderEnum; 1] = [AthProviderEnum::CoinGecko];
+}
+
+pub fn read_default_coins_config(paths: &Paths) -> Result<CoinsData> {
+    read_config(paths.coins_config_file(), COINS_MIGRATIONS)
+}
+
+pub fn write_default_coins_config(paths: &Paths, coins_data: &CoinsData) -> Result<()> {
+    write_config(paths.coins_config_file(), coins_data, COINS_MIGRATIONS)
+}
+
+pub const STABLECOIN_CATEGORY: &str = "stablecoin";
+
+// Key in the price provider data for the symbol used by the provider, when it differs from the code
+pub const PROVIDER_SYMBOL_KEY: &str = "symbol";
+
+impl CoinDef {
+    // Symbol which is sent to the price provider
+    pub fn price_provider_symbol(&self) -> &str {
+        provider_symbol(&self.code, &self.price_provider_data)
     }
 }
 
-pub fn read_default_coins_config() -> Result<CoinsData> {
-    Ok(read_json_config("conf/coins.json")?)
+pub fn provider_symbol<'a>(code: &'a str, provider_data: &'a Option<HashMap<String, String>>) -> &'a str {
+    provider_data
+        .as_ref()
+        .and_then(|data| data.get(PROVIDER_SYMBOL_KEY))
+        .map_or(code, |symbol| symbol.as_str())
 }
 
 impl CoinsData {
     pub fn get_coin_def(&self, code: &str) -> Option<&CoinDef> {
         self.coins.iter().find(|coin_def| coin_def.code == code)
     }
-}
\ No newline at end of file
+
+    pub fn get_categories(&self, code: &str) -> &[String] {
+        self.get_coin_def(code).map_or(&[], |coin_def| coin_def.categories.as_slice())
+    }
+
+    // Returns the coin and the ratio for the code. Coins with the code are preferred over aliases.
+    pub fn resolve_code(&self, code: &str) -> Option<(&CoinDef, f64)> {
+        if let Some(coin_def) = self.get_coin_def(code) {
+            return Some((coin_def, 1.0));
+        }
+        self.coins.iter().find_map(|coin_def| {
+            coin_def.aliases
+                .iter()
+                .find(|alias| alias.code == code)
+                .map(|alias| (coin_def, alias.ratio))
+        })
+    }
+
+    pub fn is_stablecoin(&self, code: &str) -> bool {
+        self.get_categories(code)
+            .iter()
+            .any(|category| category.eq_ignore_ascii_case(STABLECOIN_CATEGORY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_code() {
+        let coins_data: CoinsData = serde_json::from_value(serde_json::json!({
+            "coins": [
+                { "code": "POL", "aliases": [{ "code": "MATIC" }] },
+                { "code": "AAVE", "aliases": [{ "code": "LEND", "ratio": 0.01 }] },
+                { "code": "IOTA", "priceProviderData": { "symbol": "MIOTA" } },
+                { "code": "OLD" },
+                { "code": "NEW", "aliases": [{ "code": "OLD" }] }
+            ]
+        })).unwrap();
+
+        let (coin_def, ratio) = coins_data.resolve_code("MATIC").unwrap();
+        assert_eq!((coin_def.code.as_str(), ratio), ("POL", 1.0));
+        let (coin_def, ratio) = coins_data.resolve_code("LEND").unwrap();
+        assert_eq!((coin_def.code.as_str(), ratio), ("AAVE", 0.01));
+        assert_eq!(coins_data.resolve_code("OLD").unwrap().0.code, "OLD");
+        assert!(coins_data.resolve_code("BTC").is_none());
+
+        assert_eq!(coins_data.get_coin_def("IOTA").unwrap().price_provider_symbol(), "MIOTA");
+        assert_eq!(coins_data.get_coin_def("POL").unwrap().price_provider_symbol(), "POL");
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{ self, BufRead };
+use std::path::{ Path, PathBuf };
+use std::sync::{ Mutex, OnceLock };
+
+use argon2::{ Algorithm, Argon2, Params, Version };
+use base64::{ engine::general_purpose::STANDARD, Engine };
+use chacha20poly1305::aead::{ Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore };
+use chacha20poly1305::{ XChaCha20Poly1305, XNonce };
+use serde::{ Deserialize, Serialize };
+use tracing::{ debug, warn };
+
+use crate::Result;
+
+use super::{ write_file, ConfigFormat, CONFIG_EXTENSIONS };
+
+// Extension of the encrypted configs, for example wallets.enc
+pub const ENCRYPTED_EXTENSION: &str = "enc";
+
+pub const PASSPHRASE_ENV: &str = "CRPT_PASSPHRASE";
+pub const NEW_PASSPHRASE_ENV: &str = "CRPT_NEW_PASSPHRASE";
+
+const ENCRYPTION: &str = "argon2id-xchacha20poly1305";
+const SALT_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+
+// Argon2id costs recommended by OWASP - 19 MiB of memory, 2 iterations, 1 lane
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+// Encrypted config file. The config is encrypted in its original format, so the comments of TOML and YAML
+// configs are kept. Key derivation parameters are stored with the data, so they can be changed later.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedConfig {
+    encryption: String,
+    format: ConfigFormat,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Passphrases read from the file descriptor, one per line, used in the order in which they are needed
+static PASSPHRASE_LINES: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+
+// Passphrase of the encrypted configs, kept so the configs are decrypted and written with one prompt
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn is_encrypted(file_path: &Path) -> bool {
+    file_path.extension().is_some_and(|extension| extension == ENCRYPTED_EXTENSION)
+}
+
+// Reads the passphrases from the file descriptor, for example --passphrase-fd 3 3<passphrase.txt
+pub fn read_passphrases_from_fd(fd: i32) -> Result<()> {
+    let lines = read_fd_lines(fd)?;
+    debug!(passphrases = lines.len(), fd, "Passphrases read");
+    PASSPHRASE_LINES
+        .set(Mutex::new(lines.into()))
+        .map_err(|_| "Passphrases were already read")?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn read_fd_lines(fd: i32) -> Result<Vec<String>> {
+    use std::os::fd::FromRawFd;
+
+    // Standard streams would be closed with the file
+    if (0..=2).contains(&fd) {
+        return Err(format!("Cannot read passphrase from fd {}, use fd 3 or higher", fd).into());
+    }
+    // SAFETY: F_GETFD only reads the flags of the descriptor and fails when it is not open
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(format!("Cannot read passphrase from fd {}: {}", fd, io::Error::last_os_error()).into());
+    }
+    // SAFETY: the descriptor is open and passed to this process for the passphrases, it is owned by the file
+    // from now on and closed after reading
+    let file = unsafe { fs::File::from_raw_fd(fd) };
+    let lines = io::BufReader::new(file)
+        .lines()
+        .collect::<io::Result<Vec<String>>>()
+        .map_err(|e| format!("Cannot read passphrase from fd {}: {}", fd, e))?;
+    Ok(lines)
+}
+
+#[cfg(not(unix))]
+fn read_fd_lines(_fd: i32) -> Result<Vec<String>> {
+    Err("Reading passphrase from file descriptor is supported only on unix".into())
+}
+
+fn next_fd_passphrase() -> Option<String> {
+    PASSPHRASE_LINES.get()?.lock().ok()?.pop_front()
+}
+
+// Returns the passphrase of the encrypted configs from the env, the file descriptor or the prompt
+pub fn passphrase() -> Result<String> {
+    let mut cached = PASSPHRASE.lock().map_err(|_| "Passphrase lock failed")?;
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase = match env::var(PASSPHRASE_ENV).ok().or_else(next_fd_passphrase) {
+        Some(passphrase) => passphrase,
+        None => rpassword::prompt_password("Passphrase: ")?,
+    };
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+// Returns the new passphrase for encryption, a prompted passphrase has to be entered twice
+pub fn new_passphrase() -> Result<String> {
+    let passphrase = match env::var(NEW_PASSPHRASE_ENV).ok().or_else(next_fd_passphrase) {
+        Some(passphrase) => passphrase,
+        None => {
+            let passphrase = rpassword::prompt_password("New passphrase: ")?;
+            if rpassword::prompt_password("Repeat new passphrase: ")? != passphrase {
+                return Err("Passphrases do not match".into());
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        return Err("Passphrase cannot be empty".into());
+    }
+    *PASSPHRASE.lock().map_err(|_| "Passphrase lock failed")? = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+// Encrypts the config with a new passphrase into the file with .enc extension. The plaintext config and its
+// backups are removed. Returns the path of the encrypted file.
+pub fn encrypt_config_file(file_path: &Path) -> Result<PathBuf> {
+    if is_encrypted(file_path) {
+        return Err(format!("{} is already encrypted", file_path.display()).into());
+    }
+    let format = ConfigFormat::from_path(file_path)?;
+    let content = fs
+        ::read_to_string(file_path)
+        .map_err(|e| format!("Cannot read configuration {}: {}", file_path.display(), e))?;
+    let new_path = file_path.with_extension(ENCRYPTED_EXTENSION);
+    if new_path.exists() {
+        return Err(format!("{} already exists", new_path.display()).into());
+    }
+
+    write_file(&new_path, &encrypt_config(&content, format, &new_passphrase()?)?)?;
+    fs::remove_file(file_path)?;
+    remove_plaintext_copies(file_path)?;
+    Ok(new_path)
+}
+
+// Removes the backups and the temporary files of the config in all formats, for example wallets.json.bak left
+// by config convert. Configs in other formats are only reported, they can be edited separately.
+fn remove_plaintext_copies(file_path: &Path) -> Result<()> {
+    for extension in CONFIG_EXTENSIONS {
+        let plaintext_path = file_path.with_extension(extension);
+        for suffix in ["bak", "tmp"] {
+            let copy_path = PathBuf::from(format!("{}.{}", plaintext_path.display(), suffix));
+            if copy_path.exists() {
+                debug!(file = %copy_path.display(), "Removing plaintext copy");
+                fs::remove_file(&copy_path)?;
+            }
+        }
+        if plaintext_path.exists() {
+            warn!(file = %plaintext_path.display(), "Plaintext config is not encrypted");
+        }
+    }
+    Ok(())
+}
+
+// Writes the decrypted config into the file with the extension of its format and removes the encrypted file.
+// Returns the path of the decrypted file.
+pub fn decrypt_config_file(file_path: &Path) -> Result<PathBuf> {
+    if !is_encrypted(file_path) {
+        return Err(format!("{} is not encrypted", file_path.display()).into());
+    }
+    let (format, content) = decrypt_config(file_path, &passphrase()?)?;
+    let new_path = file_path.with_extension(format.extension());
+    if new_path.exists() {
+        return Err(format!("{} already exists", new_path.display()).into());
+    }
+
+    write_file(&new_path, &content)?;
+    fs::remove_file(file_path)?;
+    remove_backup(file_path)?;
+    Ok(new_path)
+}
+
+// Encrypts the config with a new passphrase. The backup encrypted with the old passphrase is removed.
+pub fn rekey_config_file(file_path: &Path) -> Result<()> {
+    if !is_encrypted(file_path) {
+        return Err(format!("{} is not encrypted", file_path.display()).into());
+    }
+    let (format, content) = decrypt_config(file_path, &passphrase()?)?;
+    write_file(file_path, &encrypt_config(&content, format, &new_passphrase()?)?)?;
+    remove_backup(file_path)
+}
+
+fn remove_backup(file_path: &Path) -> Result<()> {
+    let backup_path = PathBuf::from(format!("{}.bak", file_path.display()));
+    if backup_path.exists() {
+        debug!(file = %backup_path.display(), "Removing backup");
+        fs::remove_file(backup_path)?;
+    }
+    Ok(())
+}
+
+// Returns the decrypted config and its format
+pub fn decrypt_config(file_path: &Path, passphrase: &str) -> Result<(ConfigFormat, String)> {
+    let content = fs
+        ::read_to_string(file_path)
+        .map_err(|e| format!("Cannot read configuration {}: {}", file_path.display(), e))?;
+    let encrypted: EncryptedConfig = serde_json
+        ::from_str(&content)
+        .map_err(|e| format!("Invalid encrypted file {}: {}", file_path.display(), e))?;
+    if encrypted.encryption != ENCRYPTION {
+        return Err(format!("Unknown encryption {} of {}", encrypted.encryption, file_path.display()).into());
+    }
+
+    let salt = STANDARD.decode(&encrypted.salt)?;
+    let nonce = STANDARD.decode(&encrypted.nonce)?;
+    let ciphertext = STANDARD.decode(&encrypted.ciphertext)?;
+    if nonce.len() != 24 {
+        return Err(format!("Invalid nonce in {}", file_path.display()).into());
+    }
+
+    let params = Params::new(encrypted.m_cost, encrypted.t_cost, encrypted.p_cost, Some(KEY_LENGTH))
+        .map_err(|e| format!("Invalid key parameters in {}: {}", file_path.display(), e))?;
+    let cipher = derive_cipher(passphrase, &salt, params)?;
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| format!("Cannot decrypt {}, wrong passphrase or damaged file", file_path.display()))?;
+
+    Ok((encrypted.format, String::from_utf8(plaintext)?))
+}
+
+// Returns the encrypted file content of the config, with new salt and nonce
+pub fn encrypt_config(content: &str, format: ConfigFormat, passphrase: &str) -> Result<String> {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST, Some(KEY_LENGTH))
+        .map_err(|e| format!("Invalid key parameters: {}", e))?;
+    let cipher = derive_cipher(passphrase, &salt, params)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, content.as_bytes()).map_err(|_| "Encryption failed")?;
+
+    let encrypted = EncryptedConfig {
+        encryption: ENCRYPTION.to_string(),
+        format,
+        m_cost: ARGON2_M_COST,
+        t_cost: ARGON2_T_COST,
+        p_cost: ARGON2_P_COST,
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    };
+    Ok(serde_json::to_string_pretty(&encrypted)? + "\n")
+}
+
+// Returns the format of the config in the encrypted file, without decrypting it
+pub fn encrypted_format(file_path: &Path) -> Result<ConfigFormat> {
+    let content = fs::read_to_string(file_path)?;
+    let encrypted: EncryptedConfig = serde_json
+        ::from_str(&content)
+        .map_err(|e| format!("Invalid encrypted file {}: {}", file_path.display(), e))?;
+    Ok(encrypted.format)
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8], params: Params) -> Result<XChaCha20Poly1305> {
+    let mut key = [0u8; KEY_LENGTH];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_encrypt_and_decrypt_config() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("wallets.enc");
+        let content = "# Cold wallets\nversion = 1\n";
+
+        let encrypted = encrypt_config(content, ConfigFormat::Toml, "secret").unwrap();
+        assert!(!encrypted.contains("Cold wallets"));
+        fs::write(&file_path, encrypted).unwrap();
+
+        let (format, decrypted) = decrypt_config(&file_path, "secret").unwrap();
+        assert_eq!(format, ConfigFormat::Toml);
+        assert_eq!(decrypted, content);
+        assert_eq!(encrypted_format(&file_path).unwrap(), ConfigFormat::Toml);
+        assert!(decrypt_config(&file_path, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_remove_plaintext_copies() {
+        let dir = tempdir().unwrap();
+        let copies = ["wallets.json.bak", "wallets.toml.bak", "wallets.yaml.tmp"];
+        for name in copies.iter().chain(["wallets.yml", "coins.json.bak"].iter()) {
+            fs::write(dir.path().join(name), "{}").unwrap();
+        }
+
+        remove_plaintext_copies(&dir.path().join("wallets.json")).unwrap();
+        for name in copies {
+            assert!(!dir.path().join(name).exists(), "{}", name);
+        }
+        assert!(dir.path().join("wallets.yml").exists());
+        assert!(dir.path().join("coins.json.bak").exists());
+    }
+}
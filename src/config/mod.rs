@@ -1,21 +1,275 @@
-use serde::de;
-use std::fs;
+use clap::ValueEnum;
+use serde::{ de, Deserialize, Serialize };
+use serde_json::Value;
+use std::fs::{ self, File };
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use tracing::{ debug, info };
 use crate::Result;
 
+pub mod alerts;
+pub mod app;
 pub mod coins;
+pub mod crypto;
+pub mod paths;
 pub mod wallets;
 
-pub fn read_json_config<T>(file_path: &str) -> Result<T> where T: de::DeserializeOwned {
+const VERSION_KEY: &str = "version";
+
+// Converts the config of an older version, the migration at index N converts version N to N + 1.
+// The version of the config is the number of its migrations.
+pub type Migration = fn(&mut Value);
+
+// Configs written before the versioning have the same schema as the version 1
+pub fn unversioned(_config: &mut Value) {}
+
+// Format of the config file, chosen by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+// Extensions of the config files, in the order in which they are looked up
+pub const CONFIG_EXTENSIONS: [&str; 4] = ["json", "toml", "yaml", "yml"];
+
+impl ConfigFormat {
+    pub fn from_path(file_path: &Path) -> Result<Self> {
+        match file_path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("yaml" | "yml") => Ok(ConfigFormat::Yaml),
+            _ => Err(format!("Unknown config format of {}, use .json, .toml or .yaml", file_path.display()).into()),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+}
+
+// Config with its version, the version is written as the first field
+#[derive(Serialize)]
+struct VersionedConfig<'a, T> {
+    version: usize,
+    #[serde(flatten)]
+    config: &'a T,
+}
+
+pub fn read_json_config<T>(file_path: impl AsRef<Path>) -> Result<T> where T: de::DeserializeOwned {
+    let file_path = file_path.as_ref();
     // Read the contents of the file
-    println!("Reading {}", file_path);
+    debug!(file = %file_path.display(), "Reading config");
     let content = fs
         ::read_to_string(file_path)
-        .map_err(|e| format!("Cannot read coins configuration {}: {}", file_path, e))?;
+        .map_err(|e| format!("Cannot read configuration {}: {}", file_path.display(), e))?;
 
     let config: T = serde_json
         ::from_str(&content)
-        .map_err(|e| format!("Invalid json file {}: {}", file_path, e))?;
-    
-    println!("Read {}", file_path);
+        .map_err(|e| format!("Invalid json file {}: {}", file_path.display(), e))?;
+
     Ok(config)
 }
+
+// Reads the config in the format of the file extension, without migrating it.
+// Encrypted configs are decrypted with the passphrase.
+pub fn read_config_value(file_path: &Path) -> Result<Value> {
+    debug!(file = %file_path.display(), "Reading config");
+    let (format, content) = if crypto::is_encrypted(file_path) {
+        crypto::decrypt_config(file_path, &crypto::passphrase()?)?
+    } else {
+        let content = fs
+            ::read_to_string(file_path)
+            .map_err(|e| format!("Cannot read configuration {}: {}", file_path.display(), e))?;
+        (ConfigFormat::from_path(file_path)?, content)
+    };
+
+    let value = match format {
+        ConfigFormat::Json => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        ConfigFormat::Toml => toml::from_str(&content).map_err(|e| e.to_string()),
+        ConfigFormat::Yaml => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+    };
+    Ok(value.map_err(|e| format!("Invalid config file {}: {}", file_path.display(), e))?)
+}
+
+// Reads the config in the format of the file extension and migrates it from older versions
+pub fn read_config<T>(file_path: impl AsRef<Path>, migrations: &[Migration]) -> Result<T> where T: de::DeserializeOwned {
+    let file_path = file_path.as_ref();
+    let mut value = read_config_value(file_path)?;
+    migrate_config(&mut value, migrations).map_err(|e| format!("Cannot migrate {}: {}", file_path.display(), e))?;
+
+    let config: T = serde_json
+        ::from_value(value)
+        .map_err(|e| format!("Invalid config file {}: {}", file_path.display(), e))?;
+    Ok(config)
+}
+
+fn migrate_config(value: &mut Value, migrations: &[Migration]) -> Result<()> {
+    let version = value.get(VERSION_KEY).and_then(Value::as_u64).unwrap_or(0) as usize;
+    if version > migrations.len() {
+        return Err(format!("Version {} is newer than the supported version {}", version, migrations.len()).into());
+    }
+    if version < migrations.len() {
+        debug!(from = version, to = migrations.len(), "Migrating config");
+    }
+    for migration in migrations[version..].iter() {
+        migration(value);
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.remove(VERSION_KEY);
+    }
+    Ok(())
+}
+
+// Writes the config in the format of the file extension, with the version of the config.
+// Encrypted configs are written in the format of the encrypted config and encrypted with the passphrase.
+pub fn write_config<T>(file_path: impl AsRef<Path>, config: &T, migrations: &[Migration]) -> Result<()> where T: Serialize {
+    let file_path = file_path.as_ref();
+    debug!(file = %file_path.display(), "Writing config");
+    let encrypted = crypto::is_encrypted(file_path);
+    let format = match encrypted {
+        true if file_path.exists() => crypto::encrypted_format(file_path)?,
+        true => ConfigFormat::Json,
+        false => ConfigFormat::from_path(file_path)?,
+    };
+
+    let versioned = VersionedConfig { version: migrations.len(), config };
+    let mut content = match format {
+        ConfigFormat::Json => serde_json::to_string_pretty(&versioned)? + "\n",
+        ConfigFormat::Toml => toml::to_string_pretty(&versioned)?,
+        ConfigFormat::Yaml => serde_yaml::to_string(&versioned)?,
+    };
+    if encrypted {
+        content = crypto::encrypt_config(&content, format, &crypto::passphrase()?)?;
+    }
+    write_file(file_path, &content)
+}
+
+// Writes the file into a temporary file which replaces the original one, so the file is never
+// left half written. The previous version is kept in the file with .bak suffix.
+pub fn write_file(file_path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = file_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("Cannot create config dir {}: {}", dir.display(), e))?;
+    }
+
+    let tmp_path = format!("{}.tmp", file_path.display());
+    let mut file = File::create(&tmp_path).map_err(|e| format!("Cannot write configuration {}: {}", tmp_path, e))?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+
+    if file_path.exists() {
+        let backup_path = format!("{}.bak", file_path.display());
+        fs::copy(file_path, &backup_path).map_err(|e| format!("Cannot create backup {}: {}", backup_path, e))?;
+    }
+    fs::rename(&tmp_path, file_path)
+        .map_err(|e| format!("Cannot write configuration {}: {}", file_path.display(), e))?;
+    Ok(())
+}
+
+// Writes the config in the other format next to the original file, which is renamed with .bak suffix.
+// The untyped config is converted, so fields not known to T are kept, T only validates the config.
+// Returns the path of the new file.
+pub fn convert_config<T>(file_path: &Path, format: ConfigFormat, migrations: &[Migration]) -> Result<PathBuf>
+where T: de::DeserializeOwned {
+    if crypto::is_encrypted(file_path) {
+        return Err(format!("{} is encrypted, decrypt it before the conversion", file_path.display()).into());
+    }
+    let mut value = read_config_value(file_path)?;
+    migrate_config(&mut value, migrations).map_err(|e| format!("Cannot migrate {}: {}", file_path.display(), e))?;
+    serde_json::from_value::<T>(value.clone()).map_err(|e| format!("Invalid config file {}: {}", file_path.display(), e))?;
+    // TOML has no null, missing fields are read the same way
+    remove_nulls(&mut value);
+
+    let new_path = file_path.with_extension(format.extension());
+    if new_path == file_path {
+        return Err(format!("{} is already in {:?} format", file_path.display(), format).into());
+    }
+    if new_path.exists() {
+        return Err(format!("{} already exists", new_path.display()).into());
+    }
+
+    write_config(&new_path, &value, migrations)?;
+    let backup_path = format!("{}.bak", file_path.display());
+    fs::rename(file_path, &backup_path).map_err(|e| format!("Cannot create backup {}: {}", backup_path, e))?;
+    info!(from = %file_path.display(), to = %new_path.display(), "Config converted");
+    Ok(new_path)
+}
+
+fn remove_nulls(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            object.retain(|_, field| !field.is_null());
+            object.values_mut().for_each(remove_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_config_with_backup() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("config.json");
+        let file_path = file_path.to_str().unwrap();
+
+        write_config(file_path, &BTreeMap::from([("a", 1)]), &[unversioned]).unwrap();
+        assert!(!Path::new(&format!("{}.bak", file_path)).exists());
+
+        write_config(file_path, &BTreeMap::from([("a", 2)]), &[unversioned]).unwrap();
+        let config: BTreeMap<String, i32> = read_config(file_path, &[unversioned]).unwrap();
+        let backup: BTreeMap<String, i32> = read_json_config(format!("{}.bak", file_path)).unwrap();
+        assert_eq!(config, BTreeMap::from([("a".to_string(), 2)]));
+        assert_eq!(backup, BTreeMap::from([("a".to_string(), 1), ("version".to_string(), 1)]));
+        assert!(!Path::new(&format!("{}.tmp", file_path)).exists());
+    }
+
+    #[test]
+    fn test_convert_and_migrate_config() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("config.json");
+        fs::write(&file_path, r#"{ "name": "old" }"#).unwrap();
+
+        let rename: Migration = |value| {
+            if let Some(name) = value.as_object_mut().and_then(|object| object.remove("name")) {
+                value["title"] = name;
+            }
+        };
+        let migrations = [unversioned, rename];
+
+        let toml_path = convert_config::<BTreeMap<String, String>>(&file_path, ConfigFormat::Toml, &migrations).unwrap();
+        assert_eq!(fs::read_to_string(&toml_path).unwrap(), "version = 2\ntitle = \"old\"\n");
+        assert!(!file_path.exists());
+
+        let yaml_path = convert_config::<BTreeMap<String, String>>(&toml_path, ConfigFormat::Yaml, &migrations).unwrap();
+        let config: BTreeMap<String, String> = read_config(&yaml_path, &migrations).unwrap();
+        assert_eq!(config, BTreeMap::from([("title".to_string(), "old".to_string())]));
+
+        fs::write(&file_path, r#"{ "version": 3 }"#).unwrap();
+        assert!(read_config::<BTreeMap<String, String>>(&file_path, &migrations).is_err());
+    }
+
+    #[test]
+    fn test_convert_keeps_unknown_fields() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("config.json");
+        fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/conf/config.json"), &file_path).unwrap();
+
+        let toml_path = convert_config::<app::AppConfig>(&file_path, ConfigFormat::Toml, app::APP_MIGRATIONS).unwrap();
+        let value = read_config_value(&toml_path).unwrap();
+        assert_eq!(value["providers"][0]["name"], "CoinApi");
+        assert_eq!(value["currencies"], serde_json::json!(["USD", "EUR"]));
+        assert!(value.get("daemon").is_none());
+    }
+}
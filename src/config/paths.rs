@@ -0,0 +1,218 @@
+use std::env;
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+use tracing::debug;
+
+use crate::Result;
+
+use super::crypto::ENCRYPTED_EXTENSION;
+use super::CONFIG_EXTENSIONS;
+
+pub const CONFIG_DIR_ENV: &str = "CRPT_CONFIG_DIR";
+pub const DATA_DIR_ENV: &str = "CRPT_DATA_DIR";
+
+// Name of the directories in the XDG config and data dirs
+const APP_DIR_NAME: &str = "crpt-portfolio";
+
+// Directories relative to the working dir, used when the conf dir exists there
+const LEGACY_CONFIG_DIR: &str = "conf";
+const LEGACY_DATA_DIR: &str = "data";
+
+// Names of the config files without the extension
+const APP_CONFIG_FILE: &str = "config";
+const COINS_CONFIG_FILE: &str = "coins";
+const WALLETS_CONFIG_FILE: &str = "wallets";
+const ALERTS_CONFIG_FILE: &str = "alerts";
+const COIN_INFO_DIR: &str = "coins";
+const ATH_PROGRESS_FILE: &str = "ath-update-progress.json";
+const ALERTS_STATE_FILE: &str = "alerts-state.json";
+const DAEMON_LOCK_FILE: &str = "daemon.lock";
+const DAEMON_STATUS_FILE: &str = "daemon-status.json";
+
+// Profiles are subdirectories of this dir, both in the config dir and in the data dir
+const PROFILES_DIR: &str = "profiles";
+
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+
+// Directories of the configs and of the stored data. A profile has its own wallets, coins and all time data,
+// the app config and the prices are shared by all profiles.
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub profile: Option<String>,
+}
+
+impl Paths {
+    // Resolves every directory from the flag, then the env variable, then ./conf and ./data when
+    // the binary runs in a directory with the conf dir, and finally the XDG config and data dirs
+    pub fn resolve(config_dir: Option<&Path>, data_dir: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        if let Some(profile) = profile {
+            validate_profile_name(profile)?;
+        }
+        let legacy = Path::new(LEGACY_CONFIG_DIR).is_dir();
+
+        let config_dir = choose_dir(
+            config_dir,
+            env_dir(CONFIG_DIR_ENV),
+            legacy.then(|| PathBuf::from(LEGACY_CONFIG_DIR)),
+            dirs::config_dir().map(|dir| dir.join(APP_DIR_NAME))
+        ).ok_or(format!("Cannot find config dir, use --config-dir or {}", CONFIG_DIR_ENV))?;
+
+        let data_dir = choose_dir(
+            data_dir,
+            env_dir(DATA_DIR_ENV),
+            legacy.then(|| PathBuf::from(LEGACY_DATA_DIR)),
+            dirs::data_dir().map(|dir| dir.join(APP_DIR_NAME))
+        ).ok_or(format!("Cannot find data dir, use --data-dir or {}", DATA_DIR_ENV))?;
+
+        debug!(config_dir = %config_dir.display(), data_dir = %data_dir.display(), ?profile, "Resolved paths");
+        Ok(Paths { config_dir, data_dir, profile: profile.map(str::to_string) })
+    }
+
+    // Returns the paths of the same dirs with other profile, None is the default profile
+    pub fn with_profile(&self, profile: Option<&str>) -> Self {
+        Paths {
+            config_dir: self.config_dir.clone(),
+            data_dir: self.data_dir.clone(),
+            profile: profile.map(str::to_string),
+        }
+    }
+
+    pub fn profile_name(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_PROFILE_NAME)
+    }
+
+    // Returns the names of the profiles in the config dir, sorted. The default profile is not included.
+    pub fn list_profiles(&self) -> Result<Vec<String>> {
+        let profiles_dir = self.config_dir.join(PROFILES_DIR);
+        if !profiles_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut profiles = Vec::new();
+        for entry in fs::read_dir(profiles_dir)? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                profiles.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        profiles.sort();
+        Ok(profiles)
+    }
+
+    pub fn app_config_file(&self) -> PathBuf {
+        find_config_file(&self.config_dir, APP_CONFIG_FILE)
+    }
+
+    pub fn coins_config_file(&self) -> PathBuf {
+        find_config_file(&self.profile_config_dir(), COINS_CONFIG_FILE)
+    }
+
+    pub fn wallets_config_file(&self) -> PathBuf {
+        find_config_file(&self.profile_config_dir(), WALLETS_CONFIG_FILE)
+    }
+
+    pub fn alerts_config_file(&self) -> PathBuf {
+        find_config_file(&self.profile_config_dir(), ALERTS_CONFIG_FILE)
+    }
+
+    // Prices snapshots are stored directly in the data dir, they are shared by the profiles
+    pub fn prices_dir(&self) -> PathBuf {
+        self.data_dir.clone()
+    }
+
+    pub fn coin_info_dir(&self) -> PathBuf {
+        self.profile_data_dir().join(COIN_INFO_DIR)
+    }
+
+    pub fn ath_progress_file(&self) -> PathBuf {
+        self.profile_data_dir().join(ATH_PROGRESS_FILE)
+    }
+
+    pub fn alerts_state_file(&self) -> PathBuf {
+        self.profile_data_dir().join(ALERTS_STATE_FILE)
+    }
+
+    // Only one daemon runs against the data dir, whatever its profile
+    pub fn daemon_lock_file(&self) -> PathBuf {
+        self.data_dir.join(DAEMON_LOCK_FILE)
+    }
+
+    pub fn daemon_status_file(&self) -> PathBuf {
+        self.data_dir.join(DAEMON_STATUS_FILE)
+    }
+
+    fn profile_config_dir(&self) -> PathBuf {
+        match &self.profile {
+            Some(profile) => self.config_dir.join(PROFILES_DIR).join(profile),
+            None => self.config_dir.clone(),
+        }
+    }
+
+    fn profile_data_dir(&self) -> PathBuf {
+        match &self.profile {
+            Some(profile) => self.data_dir.join(PROFILES_DIR).join(profile),
+            None => self.data_dir.clone(),
+        }
+    }
+}
+
+// Profile name is a directory name, so only letters, digits, - and _ are allowed
+fn validate_profile_name(profile: &str) -> Result<()> {
+    let valid = !profile.is_empty() && profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid || profile == DEFAULT_PROFILE_NAME {
+        return Err(format!("Invalid profile name {}, use letters, digits, - and _", profile).into());
+    }
+    Ok(())
+}
+
+// Returns the existing config file, encrypted or with any of the supported extensions. A new config is json.
+fn find_config_file(dir: &Path, name: &str) -> PathBuf {
+    [ENCRYPTED_EXTENSION]
+        .iter()
+        .chain(CONFIG_EXTENSIONS.iter())
+        .map(|extension| dir.join(name).with_extension(extension))
+        .find(|file_path| file_path.exists())
+        .unwrap_or_else(|| dir.join(name).with_extension(CONFIG_EXTENSIONS[0]))
+}
+
+fn env_dir(name: &str) -> Option<PathBuf> {
+    env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from)
+}
+
+fn choose_dir(flag: Option<&Path>, env: Option<PathBuf>, legacy: Option<PathBuf>, xdg: Option<PathBuf>) -> Option<PathBuf> {
+    flag.map(Path::to_path_buf).or(env).or(legacy).or(xdg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_dir() {
+        let xdg = Some(PathBuf::from("/home/user/.config/crpt-portfolio"));
+        let legacy = Some(PathBuf::from("conf"));
+        let env = Some(PathBuf::from("/etc/crpt"));
+
+        let dir = choose_dir(Some(Path::new("my-conf")), env.clone(), legacy.clone(), xdg.clone());
+        assert_eq!(dir, Some(PathBuf::from("my-conf")));
+        assert_eq!(choose_dir(None, env.clone(), legacy.clone(), xdg.clone()), env);
+        assert_eq!(choose_dir(None, None, legacy.clone(), xdg.clone()), legacy);
+        assert_eq!(choose_dir(None, None, None, xdg.clone()), xdg);
+
+        let paths = Paths { config_dir: PathBuf::from("conf"), data_dir: PathBuf::from("data"), profile: None };
+        assert_eq!(paths.wallets_config_file(), PathBuf::from("conf/wallets.json"));
+        assert_eq!(paths.coin_info_dir(), PathBuf::from("data/coins"));
+
+        let paths = paths.with_profile(Some("treasury"));
+        assert_eq!(paths.wallets_config_file(), PathBuf::from("conf/profiles/treasury/wallets.json"));
+        assert_eq!(paths.app_config_file(), PathBuf::from("conf/config.json"));
+        assert_eq!(paths.prices_dir(), PathBuf::from("data"));
+        assert_eq!(paths.coin_info_dir(), PathBuf::from("data/profiles/treasury/coins"));
+
+        assert!(validate_profile_name("test-1").is_ok());
+        assert!(validate_profile_name("../other").is_err());
+        assert!(validate_profile_name(DEFAULT_PROFILE_NAME).is_err());
+    }
+}
@@ -1,30 +1,296 @@
-use std::path::Path;
-
-use serde::Deserialize;
+use serde::{ Deserialize, Serialize };
+use serde_json::{ Map, Value };
+use tracing::debug;
 use crate::Result;
 
-use super::read_json_config;
+use super::coins::CoinsData;
+use super::paths::Paths;
+use super::{ read_config, unversioned, write_config, Migration };
+
+// Migrations of older versions of the wallets config
+pub const WALLETS_MIGRATIONS: &[Migration] = &[unversioned];
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct WalletsData {
     pub wallets: Vec<WalletDef>,
+    // Fields unknown to this version are kept when the config is written
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WalletDef {
     pub name: String,
     pub kind: String,
     pub address: String,
+    // Custom tag of the wallet, for example cold, hot, treasury
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    // Chain of the wallet, for example Ethereum, Solana
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
     pub holdings: Vec<CoinHolding>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CoinHolding {
     pub coin: String,
     pub amount: f64,
 }
 
-pub fn read_default_wallets_config() -> Result<WalletsData> {
-    Ok(read_json_config("conf/wallets.json")?)
-}
\ No newline at end of file
+impl WalletsData {
+    // Converts the holdings in alias codes into the coins of the coins config, for example MATIC into POL.
+    // Holdings of unknown coins are kept.
+    pub fn resolve_aliases(mut self, coins_data: &CoinsData) -> Self {
+        for wallet in self.wallets.iter_mut() {
+            for holding in wallet.holdings.iter_mut() {
+                let Some((coin_def, ratio)) = coins_data.resolve_code(&holding.coin) else {
+                    continue;
+                };
+                if coin_def.code != holding.coin {
+                    debug!(wallet = %wallet.name, from = %holding.coin, to = %coin_def.code, ratio, "Converting alias");
+                    holding.coin = coin_def.code.clone();
+                    holding.amount *= ratio;
+                }
+            }
+        }
+        self
+    }
+
+    // Returns the coins of the holdings which are not in the coins config, sorted
+    pub fn missing_coins(&self, coins_data: &CoinsData) -> Vec<String> {
+        let mut missing: Vec<String> = self.wallets
+            .iter()
+            .flat_map(|wallet| wallet.holdings.iter())
+            .filter(|holding| coins_data.resolve_code(&holding.coin).is_none())
+            .map(|holding| holding.coin.clone())
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    // Changes of the wallets, new wallets and holdings are added at the end so the order of the file is kept.
+    // More wallets can have the same name with different addresses, the address selects one of them.
+    fn find_wallet_index(&self, name: &str, address: Option<&str>) -> Result<usize> {
+        let indexes: Vec<usize> = self.wallets
+            .iter()
+            .enumerate()
+            .filter(|(_, wallet)| wallet.name == name && address.is_none_or(|address| wallet.address == address))
+            .map(|(index, _)| index)
+            .collect();
+        match indexes.as_slice() {
+            [] => Err(format!("Wallet {} not found", name).into()),
+            [index] => Ok(*index),
+            _ => {
+                let addresses: Vec<&str> = indexes.iter().map(|index| self.wallets[*index].address.as_str()).collect();
+                Err(format!("More wallets are named {}, select one with --address: {}", name, addresses.join(", ")).into())
+            }
+        }
+    }
+
+    pub fn get_wallet_mut(&mut self, name: &str, address: Option<&str>) -> Result<&mut WalletDef> {
+        let index = self.find_wallet_index(name, address)?;
+        Ok(&mut self.wallets[index])
+    }
+
+    fn exists(&self, name: &str, address: &str) -> bool {
+        self.wallets.iter().any(|wallet| wallet.name == name && wallet.address == address)
+    }
+
+    pub fn add_wallet(&mut self, wallet: WalletDef) -> Result<()> {
+        if self.exists(&wallet.name, &wallet.address) {
+            return Err(format!("Wallet {} with address {} already exists", wallet.name, wallet.address).into());
+        }
+        self.wallets.push(wallet);
+        Ok(())
+    }
+
+    // Wallet with holdings is removed only with force
+    pub fn remove_wallet(&mut self, name: &str, address: Option<&str>, force: bool) -> Result<WalletDef> {
+        let index = self.find_wallet_index(name, address)?;
+        if !force && !self.wallets[index].holdings.is_empty() {
+            return Err(format!("Wallet {} has holdings, use --force to remove it", name).into());
+        }
+        Ok(self.wallets.remove(index))
+    }
+
+    pub fn rename_wallet(&mut self, name: &str, address: Option<&str>, new_name: &str) -> Result<()> {
+        let index = self.find_wallet_index(name, address)?;
+        if self.exists(new_name, &self.wallets[index].address) {
+            return Err(format!("Wallet {} with address {} already exists", new_name, self.wallets[index].address).into());
+        }
+        self.wallets[index].name = new_name.to_string();
+        Ok(())
+    }
+
+    // Sets the amount of the coin, the holding is added when the wallet does not have it
+    pub fn set_holding(&mut self, name: &str, address: Option<&str>, coin: &str, amount: f64) -> Result<f64> {
+        let wallet = self.get_wallet_mut(name, address)?;
+        match wallet.holdings.iter_mut().find(|holding| holding.coin == coin) {
+            Some(holding) => holding.amount = amount,
+            None => wallet.holdings.push(CoinHolding { coin: coin.to_string(), amount }),
+        }
+        Ok(amount)
+    }
+
+    // Adds the amount to the holding, negative amount is subtracted. Returns the new amount.
+    pub fn add_holding(&mut self, name: &str, address: Option<&str>, coin: &str, amount: f64) -> Result<f64> {
+        let current = self.get_wallet_mut(name, address)?
+            .holdings
+            .iter()
+            .find(|holding| holding.coin == coin)
+            .map_or(0.0, |holding| holding.amount);
+        let new_amount = current + amount;
+        if new_amount < 0.0 {
+            return Err(format!("Cannot subtract {} {} from {}, the wallet has {}", -amount, coin, name, current).into());
+        }
+        self.set_holding(name, address, coin, new_amount)
+    }
+
+    pub fn remove_holding(&mut self, name: &str, address: Option<&str>, coin: &str) -> Result<()> {
+        let wallet = self.get_wallet_mut(name, address)?;
+        let index = wallet.holdings
+            .iter()
+            .position(|holding| holding.coin == coin)
+            .ok_or_else(|| format!("Wallet {} has no {}", name, coin))?;
+        wallet.holdings.remove(index);
+        Ok(())
+    }
+
+    // Returns the problems of the wallets - empty names, kinds and coins, duplicate wallets, invalid amounts
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (index, wallet) in self.wallets.iter().enumerate() {
+            if wallet.name.trim().is_empty() {
+                problems.push(format!("Wallet {} has empty name", index + 1));
+            }
+            let duplicate = self.wallets[..index]
+                .iter()
+                .any(|other| other.name == wallet.name && other.address == wallet.address);
+            if duplicate {
+                problems.push(format!("Duplicate wallet {} with address {}", wallet.name, wallet.address));
+            }
+            if wallet.kind.trim().is_empty() {
+                problems.push(format!("Wallet {} has empty kind", wallet.name));
+            }
+            for holding in wallet.holdings.iter() {
+                if holding.coin.trim().is_empty() {
+                    problems.push(format!("Wallet {} has holding with empty coin", wallet.name));
+                }
+                if !holding.amount.is_finite() || holding.amount < 0.0 {
+                    problems.push(format!("Wallet {} has invalid amount {} of {}", wallet.name, holding.amount, holding.coin));
+                }
+            }
+        }
+        problems
+    }
+}
+
+pub fn read_default_wallets_config(paths: &Paths) -> Result<WalletsData> {
+    read_config(paths.wallets_config_file(), WALLETS_MIGRATIONS)
+}
+
+// Validates the wallets and writes them, the previous file is kept as backup
+pub fn write_default_wallets_config(paths: &Paths, wallets_data: &WalletsData) -> Result<()> {
+    let problems = wallets_data.validate();
+    if !problems.is_empty() {
+        return Err(format!("Invalid wallets, not written: {}", problems.join(", ")).into());
+    }
+    write_config(paths.wallets_config_file(), wallets_data, WALLETS_MIGRATIONS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_coins_and_aliases() {
+        let coins_data: CoinsData = serde_json::from_value(serde_json::json!({
+            "coins": [
+                { "code": "BTC" },
+                { "code": "AAVE", "aliases": [{ "code": "LEND", "ratio": 0.01 }] }
+            ]
+        })).unwrap();
+        let wallets: WalletsData = serde_json::from_value(serde_json::json!({
+            "wallets": [{
+                "name": "Ledger",
+                "kind": "Ledger",
+                "address": "-",
+                "holdings": [
+                    { "coin": "BTC", "amount": 1.0 },
+                    { "coin": "LEND", "amount": 1000.0 },
+                    { "coin": "XYZ", "amount": 5.0 },
+                    { "coin": "XYZ", "amount": 6.0 }
+                ]
+            }]
+        })).unwrap();
+
+        assert_eq!(wallets.missing_coins(&coins_data), vec!["XYZ"]);
+
+        let wallets = wallets.resolve_aliases(&coins_data);
+        let aave = &wallets.wallets[0].holdings[1];
+        assert_eq!(aave.coin, "AAVE");
+        assert_eq!(aave.amount, 10.0);
+    }
+
+    #[test]
+    fn test_wallet_changes() {
+        let mut wallets = WalletsData { wallets: Vec::new(), extra: Map::new() };
+        let wallet = |name: &str, address: &str| WalletDef {
+            name: name.to_string(),
+            kind: "Ledger".to_string(),
+            address: address.to_string(),
+            tag: None,
+            chain: None,
+            holdings: Vec::new(),
+            extra: Map::new(),
+        };
+        wallets.add_wallet(wallet("Ledger", "btc")).unwrap();
+        wallets.add_wallet(wallet("Ledger", "eth")).unwrap();
+        wallets.add_wallet(wallet("Kraken", "kraken")).unwrap();
+        assert!(wallets.add_wallet(wallet("Ledger", "btc")).is_err());
+
+        assert!(wallets.set_holding("Ledger", None, "BTC", 1.0).is_err());
+        wallets.set_holding("Ledger", Some("btc"), "BTC", 1.0).unwrap();
+        assert_eq!(wallets.add_holding("Ledger", Some("btc"), "BTC", 0.5).unwrap(), 1.5);
+        assert_eq!(wallets.add_holding("Kraken", None, "ETH", 2.0).unwrap(), 2.0);
+        assert!(wallets.add_holding("Kraken", None, "ETH", -3.0).is_err());
+        assert!(wallets.remove_wallet("Kraken", None, false).is_err());
+
+        wallets.rename_wallet("Ledger", Some("eth"), "Ledger ETH").unwrap();
+        wallets.remove_holding("Ledger", None, "BTC").unwrap();
+        assert!(wallets.remove_holding("Ledger", None, "BTC").is_err());
+
+        let names: Vec<&str> = wallets.wallets.iter().map(|wallet| wallet.name.as_str()).collect();
+        assert_eq!(names, vec!["Ledger", "Ledger ETH", "Kraken"]);
+        assert!(wallets.validate().is_empty());
+
+        wallets.wallets[1].name = "Ledger".to_string();
+        wallets.wallets[1].address = "btc".to_string();
+        wallets.wallets[2].holdings[0].amount = -1.0;
+        assert_eq!(wallets.validate().len(), 2);
+
+        let json = serde_json::to_value(&wallets).unwrap();
+        assert!(json["wallets"][0].get("tag").is_none());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("wallets.toml");
+        let wallets: WalletsData = serde_json::from_value(serde_json::json!({
+            "wallets": [{ "name": "Ledger", "kind": "Ledger", "address": "-", "holdings": [], "note": "cold storage" }],
+            "owner": "me"
+        })).unwrap();
+        write_config(&file_path, &wallets, WALLETS_MIGRATIONS).unwrap();
+
+        let wallets: WalletsData = read_config(&file_path, WALLETS_MIGRATIONS).unwrap();
+        assert_eq!(wallets.extra.get("owner"), Some(&Value::from("me")));
+        assert_eq!(wallets.wallets[0].extra.get("note"), Some(&Value::from("cold storage")));
+        assert!(!wallets.extra.contains_key("version"));
+    }
+}
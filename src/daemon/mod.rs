@@ -0,0 +1,234 @@
+use std::fs::{ self, File, OpenOptions, TryLockError };
+use std::io::{ Read, Write };
+use std::str::FromStr;
+
+use chrono::Local;
+use cron::Schedule;
+use tracing::{ debug, error, info, warn };
+
+use crate::alerts;
+use crate::coins;
+use crate::config::app::read_default_app_config;
+use crate::config::paths::Paths;
+use crate::Result;
+
+mod status;
+
+use status::DaemonStatus;
+
+// Schedules from the command line, the schedules from the app config are used when not set
+#[derive(Debug)]
+pub struct DaemonOptions {
+    pub prices_schedule: Option<String>,
+    pub ath_schedule: Option<String>,
+    // Run all jobs at the start, before waiting for their schedules
+    pub run_now: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Job {
+    Prices,
+    Ath,
+}
+
+impl Job {
+    fn name(&self) -> &'static str {
+        match self {
+            Job::Prices => "prices",
+            Job::Ath => "ath",
+        }
+    }
+}
+
+// Runs the jobs by their schedules until SIGTERM or Ctrl-C. A job interrupted by the shutdown is recorded as failed,
+// the interrupted update of all time data continues at its next run.
+pub async fn run_daemon(paths: &Paths, options: &DaemonOptions) -> Result<()> {
+    let _lock = lock_data_dir(paths)?;
+    let mut shutdown = Shutdown::new()?;
+
+    let app_config = read_default_app_config(paths)?;
+    let jobs = [
+        (Job::Prices, parse_schedule(options.prices_schedule.as_deref().unwrap_or(&app_config.daemon.prices_schedule))?),
+        (Job::Ath, parse_schedule(options.ath_schedule.as_deref().unwrap_or(&app_config.daemon.ath_schedule))?),
+    ];
+
+    let now = Local::now();
+    let mut status = DaemonStatus::new(paths);
+    let mut next_runs = Vec::new();
+    for (job, schedule) in jobs.iter() {
+        let next_run = if options.run_now { Some(now) } else { schedule.after(&now).next() };
+        status.add_job(job.name(), schedule.source(), next_run);
+        next_runs.push(next_run);
+    }
+    write_status(paths, &status);
+    info!(pid = status.pid, data_dir = %paths.data_dir.display(), "Daemon started");
+
+    loop {
+        let Some((index, next_run)) = next_runs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, next_run)| next_run.map(|next_run| (index, next_run)))
+            .min_by_key(|(_, next_run)| *next_run) else {
+            info!("No more scheduled runs");
+            break;
+        };
+        let (job, schedule) = &jobs[index];
+
+        debug!(job = job.name(), %next_run, "Waiting for the next run");
+        let wait = (next_run - Local::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = shutdown.wait() => break,
+            _ = tokio::time::sleep(wait) => {}
+        }
+
+        let started = Local::now();
+        info!(job = job.name(), "Job started");
+        let (result, stop) = tokio::select! {
+            result = run_job(paths, *job) => (result, false),
+            _ = shutdown.wait() => (Err("Interrupted by shutdown".into()), true),
+        };
+        match &result {
+            Ok(()) => info!(job = job.name(), "Job finished"),
+            Err(e) => error!(job = job.name(), error = %e, "Job failed"),
+        }
+
+        // Runs missed during a long job are skipped
+        next_runs[index] = schedule.after(&Local::now()).next();
+        status.record_run(job.name(), started, &result, next_runs[index]);
+        write_status(paths, &status);
+        if stop {
+            break;
+        }
+    }
+
+    status.stopped = Some(Local::now());
+    write_status(paths, &status);
+    info!("Daemon stopped");
+    Ok(())
+}
+
+async fn run_job(paths: &Paths, job: Job) -> Result<()> {
+    // Currencies are read at every run, so the changed app config is used without a restart
+    let app_config = read_default_app_config(paths)?;
+    match job {
+        Job::Prices => {
+            coins::update_prices::update_coins_prices(paths, &app_config.currencies).await?;
+            alerts::check_alerts(paths).await
+        }
+        Job::Ath => coins::update_ath::update_ath_data_for_all_tokens(paths, &app_config.currencies, false).await,
+    }
+}
+
+// The daemon keeps running when the status cannot be written
+fn write_status(paths: &Paths, status: &DaemonStatus) {
+    if let Err(e) = status.write(paths) {
+        warn!(file = %paths.daemon_status_file().display(), error = %e, "Cannot write daemon status");
+    }
+}
+
+// Cron expression with seconds, or the standard one without seconds - for example "*/15 * * * *"
+fn parse_schedule(expression: &str) -> Result<Schedule> {
+    let with_seconds = match expression.split_whitespace().count() {
+        5 => format!("0 {}", expression),
+        _ => expression.to_string(),
+    };
+    Ok(Schedule::from_str(&with_seconds).map_err(|e| format!("Invalid schedule \"{}\": {}", expression, e))?)
+}
+
+// Locks the lock file in the data dir, so only one daemon updates the data. The lock is released when the returned
+// file is closed, also when the daemon is killed. The file contains the pid of the daemon.
+pub fn lock_data_dir(paths: &Paths) -> Result<File> {
+    let lock_path = paths.daemon_lock_file();
+    fs::create_dir_all(&paths.data_dir)
+        .map_err(|e| format!("Cannot create data dir {}: {}", paths.data_dir.display(), e))?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .map_err(|e| format!("Cannot open lock file {}: {}", lock_path.display(), e))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let mut pid = String::new();
+            file.read_to_string(&mut pid).ok();
+            return Err(
+                format!("Data dir {} is updated by another process with pid {}", paths.data_dir.display(), pid.trim()).into()
+            );
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(format!("Cannot lock {}: {}", lock_path.display(), e).into());
+        }
+    }
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    Ok(file)
+}
+
+// Signal handlers are installed at the start, so the signals received during a job are not lost
+#[cfg(unix)]
+pub struct Shutdown {
+    terminate: tokio::signal::unix::Signal,
+    interrupt: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Shutdown {
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{ signal, SignalKind };
+
+        Ok(Shutdown {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    pub async fn wait(&mut self) {
+        tokio::select! {
+            _ = self.terminate.recv() => info!("SIGTERM received, stopping"),
+            _ = self.interrupt.recv() => info!("SIGINT received, stopping"),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub struct Shutdown;
+
+#[cfg(not(unix))]
+impl Shutdown {
+    pub fn new() -> Result<Self> {
+        Ok(Shutdown)
+    }
+
+    pub async fn wait(&mut self) {
+        tokio::signal::ctrl_c().await.ok();
+        info!("Ctrl-C received, stopping");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_schedule_and_lock() {
+        let after = Local.with_ymd_and_hms(2025, 1, 10, 10, 7, 30).unwrap();
+        let schedule = parse_schedule("*/15 * * * *").unwrap();
+        assert_eq!(schedule.after(&after).next(), Some(Local.with_ymd_and_hms(2025, 1, 10, 10, 15, 0).unwrap()));
+        let schedule = parse_schedule("30 0 3 * * *").unwrap();
+        assert_eq!(schedule.after(&after).next(), Some(Local.with_ymd_and_hms(2025, 1, 11, 3, 0, 30).unwrap()));
+        assert!(parse_schedule("every hour").is_err());
+
+        let dir = tempdir().unwrap();
+        let paths = Paths { config_dir: dir.path().join("conf"), data_dir: dir.path().join("data"), profile: None };
+        let lock = lock_data_dir(&paths).unwrap();
+        let error = lock_data_dir(&paths).unwrap_err().to_string();
+        assert!(error.contains(&std::process::id().to_string()), "{}", error);
+        drop(lock);
+        assert!(lock_data_dir(&paths).is_ok());
+    }
+}
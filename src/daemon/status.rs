@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use chrono::{ DateTime, Local };
+use serde::Serialize;
+
+use crate::coins::filestore::write_data_json_to_file;
+use crate::config::paths::Paths;
+use crate::Result;
+
+// Status of the running daemon, written at the start, after every run and at the stop
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub profile: String,
+    pub started: DateTime<Local>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<DateTime<Local>>,
+    pub jobs: BTreeMap<String, JobStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatus {
+    pub schedule: String,
+    pub next_run: Option<DateTime<Local>>,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run: Option<RunStatus>,
+}
+
+// Outcome of the last run of the job
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunStatus {
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DaemonStatus {
+    pub fn new(paths: &Paths) -> Self {
+        DaemonStatus {
+            pid: std::process::id(),
+            profile: paths.profile_name().to_string(),
+            started: Local::now(),
+            stopped: None,
+            jobs: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_job(&mut self, job: &str, schedule: &str, next_run: Option<DateTime<Local>>) {
+        self.jobs.insert(job.to_string(), JobStatus {
+            schedule: schedule.to_string(),
+            next_run,
+            runs: 0,
+            failures: 0,
+            last_run: None,
+        });
+    }
+
+    pub fn record_run(&mut self, job: &str, started: DateTime<Local>, result: &Result<()>, next_run: Option<DateTime<Local>>) {
+        if let Some(job_status) = self.jobs.get_mut(job) {
+            job_status.runs += 1;
+            if result.is_err() {
+                job_status.failures += 1;
+            }
+            job_status.next_run = next_run;
+            job_status.last_run = Some(RunStatus {
+                started,
+                finished: Local::now(),
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+            });
+        }
+    }
+
+    pub fn write(&self, paths: &Paths) -> Result<()> {
+        write_data_json_to_file(paths.daemon_status_file(), self)
+    }
+}
@@ -1,26 +1,57 @@
-use std::str::FromStr;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use client::coingecko::{Coin, CoinGeckoClient};
+use chrono::Local;
 
-use config::wallets;
+use clap::{ ArgAction, Args, ValueEnum };
+use config::wallets::{ self, WalletDef, WalletsData };
 use dotenv::dotenv;
-use model::{ Currency, ReportOrder, ReportSortBy };
-use serde_json::to_writer_pretty;
-use service::{ list_wallets, report_holdings, write_report, write_wallets_report, ReportFilter };
+use config::app::{ read_default_app_config, AppConfig };
+use config::alerts::AlertsConfig;
+use config::{ convert_config, crypto, ConfigFormat };
+use config::coins::{ read_default_coins_config, CoinsData };
+use config::paths::Paths;
+use daemon::DaemonOptions;
+use tui::TuiOptions;
+use model::{ AllocationBy, AthInfo, Currency, GroupBy, OutputFormat, PriceInfo, ReportOrder, ReportSortBy };
+use service::{
+    allocation_summary,
+    group_report_lines,
+    holding_lines,
+    list_wallets,
+    portfolio_value_history,
+    write_ath_history,
+    write_html_report,
+    write_report,
+    write_wallets_report,
+    HoldingsReport,
+    NamePattern,
+    ReportFilter,
+    WalletsReport,
+};
+use tracing::{ debug, info, warn };
 
 pub type Result<T> = core::result::Result<T, Error>;
 type Error = Box<dyn std::error::Error>;
 
+mod alerts;
+mod api;
 mod client;
 mod model;
 mod service;
 mod coins;
 mod config;
+mod daemon;
+mod metrics;
 mod provider;
+mod tui;
 mod utils;
 
 use clap::{ Parser, Subcommand };
-use utils::coin_list;
+use utils::coin_list::{ self, ResolveIdsOptions };
+use utils::logging;
 
 /// Simple portfolio viewer
 #[derive(Parser, Debug)]
@@ -28,64 +59,487 @@ use utils::coin_list;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// More verbose logging to stderr, can be repeated (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Less logging to stderr, can be repeated (-q errors only, -qq nothing)
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    quiet: u8,
+
+    /// Append logs as JSON lines to this file
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Directory with wallets.json, coins.json and config.json. Overrides CRPT_CONFIG_DIR
+    #[arg(long, global = true)]
+    config_dir: Option<PathBuf>,
+
+    /// Directory of the stored prices and all time data. Overrides CRPT_DATA_DIR
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+
+    /// Read the passphrase of encrypted configs from this file descriptor, one passphrase per line.
+    /// The new passphrase of encrypt and rekey is on the next line
+    #[arg(long, global = true)]
+    passphrase_fd: Option<i32>,
+
+    /// Profile with own wallets, coins and all time data in profiles/<name> of the config and data dirs.
+    /// Prices are shared by all profiles
+    #[arg(short = 'p', long, global = true)]
+    profile: Option<String>,
+}
+
+/// Filters and ordering of the holdings report
+#[derive(Args, Debug)]
+struct HoldingsFilterArgs {
+    /// Name of the token - for example ETH, BTC. Can be repeated or comma separated
+    #[arg(short, long, value_delimiter = ',')]
+    token: Vec<String>,
+
+    /// Tokens to leave out of the report. Can be repeated or comma separated
+    #[arg(long, value_delimiter = ',')]
+    exclude_token: Vec<String>,
+
+    /// Wallet name - for example "Ethereum MetaMask 1". Case-insensitive glob like "*metamask*"
+    /// or regex with re: prefix like "re:^ethereum". Can be repeated
+    #[arg(short = 'n', long)]
+    wallet_name: Vec<NamePattern>,
+
+    /// Wallet names to leave out of the report, same patterns as --wallet-name. Can be repeated
+    #[arg(long)]
+    exclude_wallet: Vec<NamePattern>,
+
+    /// Wallet kind - for example Ledger, MetaMask. Can be repeated or comma separated
+    #[arg(short = 'k', long, value_delimiter = ',')]
+    wallet_kind: Vec<String>,
+
+    /// Wallet kinds to leave out of the report. Can be repeated or comma separated
+    #[arg(long, value_delimiter = ',')]
+    exclude_kind: Vec<String>,
+
+    /// Wallet address - address for the wallet. Can be repeated or comma separated
+    #[arg(short = 'a', long, value_delimiter = ',')]
+    wallet_address: Vec<String>,
+
+    /// Hide report lines with smaller value, for example --min-value 10 hides the dust
+    #[arg(long)]
+    min_value: Option<f64>,
+
+    /// Hide report lines with larger value
+    #[arg(long)]
+    max_value: Option<f64>,
+
+    /// currency
+    #[arg(short, long, default_value = "usd")]
+    currency: Currency,
+
+    /// group by token, when set, the tokens in different wallets are grouped per token. Same as --group-by token
+    #[arg(short, long, default_value = "false", conflicts_with = "group_by")]
+    group_by_token: bool,
+
+    /// Group the holdings, levels can be nested - for example --group-by kind,token
+    #[arg(long, value_enum, value_delimiter = ',')]
+    group_by: Vec<GroupBy>,
+
+    /// Sort field for report
+    #[arg(long, default_value = "value", value_enum)]
+    sort_by: ReportSortBy,
+
+    /// Sort order for report
+    #[arg(long, default_value = "desc", value_enum)]
+    order: ReportOrder,
+}
+
+impl HoldingsFilterArgs {
+    fn to_report_filter(&self) -> ReportFilter {
+        let group_by = if self.group_by_token { vec![GroupBy::Token] } else { self.group_by.clone() };
+        ReportFilter {
+            tokens: self.token.clone(),
+            exclude_tokens: self.exclude_token.clone(),
+            wallet_names: self.wallet_name.clone(),
+            exclude_wallet_names: self.exclude_wallet.clone(),
+            wallet_kinds: self.wallet_kind.clone(),
+            exclude_wallet_kinds: self.exclude_kind.clone(),
+            wallet_addresses: self.wallet_address.clone(),
+            min_value: self.min_value,
+            max_value: self.max_value,
+            currency: self.currency.clone(),
+            group_by,
+            sort_by: self.sort_by.clone(),
+            order: self.order.clone(),
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Report holdings for wallet or token
     Holdings {
-        /// Name of the token in uppercase - for example ETH, BTC
-        #[arg(short, long)]
-        token: Option<String>,
+        #[command(flatten)]
+        filter: HoldingsFilterArgs,
 
-        /// Wallet name - for example Ethereum MetaMask 1
-        #[arg(short = 'n', long)]
-        wallet_name: Option<String>,
+        /// Output format of the report
+        #[arg(short, long, default_value = "table", value_enum)]
+        output: OutputFormat,
 
-        /// Wallet kind - for example Ledger, MetaMask
-        #[arg(short = 'k', long)]
-        wallet_kind: Option<String>,
+        /// Add summary with concentration metrics - top N share, Herfindahl index and stablecoin share
+        #[arg(long, default_value = "false")]
+        summary: bool,
 
-        /// Wallet address - address for the wallet
-        #[arg(short = 'a', long)]
-        wallet_address: Option<String>,
+        /// Number of the largest tokens in the summary
+        #[arg(long, default_value = "5")]
+        top_n: usize,
 
-        /// currency
-        #[arg(short, long, default_value = "usd")]
-        currency: Currency,
+        /// Add allocation breakdown by wallet kind or by token categories from coins config, implies --summary
+        #[arg(long, value_enum)]
+        allocation_by: Option<AllocationBy>,
 
-        /// group by token, when set, the tokens in different wallets are grouped per token
-        #[arg(short, long, default_value = "false")]
-        group_by_token: bool,
+        /// Add ATH, distance from ATH, ATL and value at ATH columns from stored all time data
+        #[arg(long, default_value = "false")]
+        ath: bool,
 
-        /// Sort field for report
-        #[arg(long, default_value = "value", value_enum)]
-        sort_by: ReportSortBy,
+        /// Consolidated report of all profiles, wallet names are prefixed with the profile name
+        #[arg(long, default_value = "false")]
+        all_profiles: bool,
 
-        /// Sort order for report
-        #[arg(long, default_value = "desc", value_enum)]
-        order: ReportOrder,
+        /// Fetch the prices every given seconds and redraw the table with the value changes since the previous refresh
+        #[arg(long, value_name = "SECONDS")]
+        watch: Option<u64>,
     },
 
+    /// Write holdings report with allocation and value charts into a single html file
+    HtmlReport {
+        #[command(flatten)]
+        filter: HoldingsFilterArgs,
+
+        /// Output html file
+        #[arg(long, default_value = "portfolio.html")]
+        out: PathBuf,
+    },
+
+    /// List profiles, the default profile is listed when it has wallets
+    Profiles {},
+
     /// List wallets
     ListWallets {
-        /// Wallet name - for example Ethereum MetaMask 1
+        /// Print only the wallet names, one per line
         #[arg(short = 'n', long)]
         wallet_names: bool,
+
+        /// Output format of the report
+        #[arg(short, long, default_value = "table", value_enum)]
+        output: OutputFormat,
     },
 
-    /// Update all time data like ATH, ATL ... This might take a while
+    /// Update all time data like ATH, ATL ... An interrupted update of all tokens continues where it stopped
     UpdateAllTimeData {
         /// Name of the token in uppercase - for example ETH, BTC
         #[arg(short, long)]
         token: Option<String>,
+
+        /// Update all tokens again, instead of continuing the interrupted update
+        #[arg(long, default_value = "false", conflicts_with = "token")]
+        restart: bool,
+    },
+
+    /// Show stored all time data of the token over time
+    AthHistory {
+        /// Name of the token in uppercase - for example ETH, BTC
+        #[arg(short, long)]
+        token: String,
+
+        /// Currency of the all time data
+        #[arg(short, long, default_value = "usd", value_enum)]
+        currency: Currency,
+
+        /// Output format of the history
+        #[arg(short, long, default_value = "table", value_enum)]
+        output: OutputFormat,
     },
 
     /// Update actual prices for all coins
     UpdatePrices {},
 
-    /// Update coins data with downloaded data from CoinGecko which is stored in data/list.json
-    UpdateCoinsWithList {},
+    /// Manage coins config
+    Coins {
+        #[command(subcommand)]
+        command: CoinsCommands,
+    },
+
+    /// Add, remove or rename wallets in wallets.json
+    Wallet {
+        #[command(subcommand)]
+        command: WalletCommands,
+    },
+
+    /// Change holdings of a wallet in wallets.json
+    Holding {
+        #[command(subcommand)]
+        command: HoldingCommands,
+    },
+
+    /// Check alert rules from the alerts config or send a test alert to the sinks
+    Alerts {
+        #[command(subcommand)]
+        command: AlertsCommands,
+    },
+
+    /// Manage config files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Update prices and all time data by the schedules in the app config until SIGTERM or Ctrl-C.
+    /// Only one daemon runs with the data dir, the outcome of the runs is in daemon-status.json
+    Daemon {
+        /// Cron schedule of the prices update, for example "*/15 * * * *". Overrides the app config
+        #[arg(long)]
+        prices_schedule: Option<String>,
+
+        /// Cron schedule of the all time data update, for example "0 3 * * *". Overrides the app config
+        #[arg(long)]
+        ath_schedule: Option<String>,
+
+        /// Run both updates at the start
+        #[arg(long, default_value = "false")]
+        run_now: bool,
+    },
+
+    /// Serve holdings, wallets, prices and all time data as JSON over HTTP, with OpenAPI spec at /openapi.json.
+    /// Requests need Authorization: Bearer <token> when CRPT_API_TOKEN is set
+    Serve {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: SocketAddr,
+    },
+
+    /// Serve portfolio values, prices and provider requests as Prometheus metrics at /metrics
+    Metrics {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:9184")]
+        bind: SocketAddr,
+
+        /// Update the prices every given seconds, instead of running the daemon. Needed for the provider metrics
+        #[arg(long)]
+        update_every: Option<u64>,
+    },
+
+    /// Interactive dashboard with holdings, token details with price history and wallets, refreshing the prices
+    /// in the background
+    Tui {
+        /// Currency of the values
+        #[arg(short, long, default_value = "usd")]
+        currency: Currency,
+
+        /// Seconds between the refreshes
+        #[arg(long, default_value = "300")]
+        refresh: u64,
+
+        /// Do not fetch the prices, only reload the stored prices updated by the daemon
+        #[arg(long, default_value = "false")]
+        no_fetch: bool,
+    },
+
+    /// Check configs, API keys, data dir and providers
+    Doctor {
+        /// Do not send requests to the providers
+        #[arg(long)]
+        offline: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AlertsCommands {
+    /// Evaluate the rules with the latest stored prices, they are also evaluated after update-prices
+    Check {},
+
+    /// Send a test alert to all sinks
+    Test {},
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Convert config files to other format, the original files are kept with .bak suffix
+    Convert {
+        /// Format of the converted files
+        #[arg(long, value_enum)]
+        to: ConfigFormat,
+
+        /// Config to convert, all configs when not set
+        #[arg(long, value_enum)]
+        file: Option<ConfigFile>,
+    },
+
+    /// Encrypt the config with a passphrase, the plaintext config is removed
+    Encrypt {
+        /// Config to encrypt
+        #[arg(long, value_enum, default_value = "wallets")]
+        file: ConfigFile,
+    },
+
+    /// Decrypt the encrypted config, the encrypted file is removed
+    Decrypt {
+        /// Config to decrypt
+        #[arg(long, value_enum, default_value = "wallets")]
+        file: ConfigFile,
+    },
+
+    /// Encrypt the encrypted config with a new passphrase
+    Rekey {
+        /// Config to encrypt with the new passphrase
+        #[arg(long, value_enum, default_value = "wallets")]
+        file: ConfigFile,
+    },
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum ConfigFile {
+    Wallets,
+    Coins,
+    App,
+    Alerts,
+}
+
+#[derive(Subcommand, Debug)]
+enum WalletCommands {
+    /// Add a new wallet at the end of the wallets
+    Add {
+        /// Unique wallet name - for example "Ethereum MetaMask 1"
+        #[arg(short, long)]
+        name: String,
+
+        /// Wallet kind - for example Ledger, MetaMask
+        #[arg(short, long)]
+        kind: String,
+
+        /// Wallet address
+        #[arg(short, long)]
+        address: String,
+
+        /// Custom tag, for example cold, hot
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Chain of the wallet, for example Ethereum
+        #[arg(long)]
+        chain: Option<String>,
+    },
+
+    /// Remove the wallet
+    Remove {
+        #[command(flatten)]
+        wallet: WalletArgs,
+
+        /// Remove also the wallet with holdings
+        #[arg(long, default_value = "false")]
+        force: bool,
+    },
+
+    /// Rename the wallet
+    Rename {
+        #[command(flatten)]
+        wallet: WalletArgs,
+
+        #[arg(long)]
+        new_name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HoldingCommands {
+    /// Set the amount of the coin in the wallet
+    Set {
+        #[command(flatten)]
+        holding: HoldingArgs,
+
+        #[arg(long)]
+        amount: f64,
+    },
+
+    /// Add the amount to the coin in the wallet, negative amount is subtracted
+    Add {
+        #[command(flatten)]
+        holding: HoldingArgs,
+
+        #[arg(long, allow_negative_numbers = true)]
+        amount: f64,
+    },
+
+    /// Remove the coin from the wallet
+    Remove {
+        #[command(flatten)]
+        holding: HoldingArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+struct WalletArgs {
+    /// Wallet name
+    #[arg(short, long)]
+    name: String,
+
+    /// Wallet address, needed when more wallets have the same name
+    #[arg(short, long)]
+    address: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct HoldingArgs {
+    /// Wallet name
+    #[arg(short, long)]
+    wallet: String,
+
+    /// Wallet address, needed when more wallets have the same name
+    #[arg(short, long)]
+    address: Option<String>,
+
+    /// Coin code - for example BTC, it is converted to uppercase
+    #[arg(short, long)]
+    coin: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum CoinsCommands {
+    /// Resolve CoinGecko ids of the coins by symbol, name hints and market cap and show the changes
+    ResolveIds {
+        /// Resolve also the coins which already have the id
+        #[arg(long, default_value = "false")]
+        all: bool,
+
+        /// Choose the id of ambiguous coins on the terminal
+        #[arg(short, long, default_value = "false")]
+        interactive: bool,
+
+        /// Use the candidate with the largest market cap for ambiguous coins
+        #[arg(long, default_value = "false", conflicts_with = "interactive")]
+        pick_top: bool,
+
+        /// Id for the coin, for example --prefer UNI=uniswap. Can be repeated
+        #[arg(long, value_parser = parse_preferred_id)]
+        prefer: Vec<(String, String)>,
+
+        /// Coin list downloaded from CoinGecko /coins/list, used instead of calling the api
+        #[arg(long)]
+        list_file: Option<PathBuf>,
+
+        /// Write the changes into coins.json, otherwise only the preview is shown
+        #[arg(long, default_value = "false")]
+        write: bool,
+    },
+
+    /// Add coins from wallets which are missing in the coins config, with the price provider which lists them
+    Sync {
+        /// Coin list downloaded from CoinGecko /coins/list, used instead of calling the api
+        #[arg(long)]
+        list_file: Option<PathBuf>,
+
+        /// Write the new coins into coins.json, otherwise only the preview is shown
+        #[arg(long, default_value = "false")]
+        write: bool,
+    },
 }
 
 #[tokio::main]
@@ -93,13 +547,43 @@ async fn main() -> Result<()> {
     dotenv().ok();
 
     let cli = Cli::parse();
+    // The dashboard owns the terminal, so it logs only into the log file
+    let console = !matches!(cli.command, Commands::Tui { .. });
+    logging::init_logging(cli.verbose, cli.quiet, cli.log_file.as_deref(), console)?;
+    if let Some(fd) = cli.passphrase_fd {
+        crypto::read_passphrases_from_fd(fd)?;
+    }
+    let paths = Paths::resolve(cli.config_dir.as_deref(), cli.data_dir.as_deref(), cli.profile.as_deref())?;
+
+    // Only the reports of the wallets check the coins, holding commands check them after the change.
+    // Encrypted wallets are not decrypted only for the warning.
+    let reads_wallets = matches!(cli.command, Commands::Holdings { .. } | Commands::HtmlReport { .. } | Commands::ListWallets { .. });
+    if reads_wallets && !crypto::is_encrypted(&paths.wallets_config_file()) {
+        warn_missing_coins(&paths);
+    }
 
     match &cli.command {
-        Commands::Holdings { .. } => handle_holdings(&cli.command).await?,
-        Commands::ListWallets { .. } => handle_list_wallets(&cli.command).await?,
-        Commands::UpdatePrices { .. } => update_prices(&cli.command).await?,
-        Commands::UpdateAllTimeData { .. } => update_all_time_data(&cli.command).await?,
-        Commands::UpdateCoinsWithList { .. } => update_coins_with_list(&cli.command).await?,
+        Commands::Holdings { .. } => handle_holdings(&paths, &cli.command).await?,
+        Commands::HtmlReport { .. } => handle_html_report(&paths, &cli.command).await?,
+        Commands::ListWallets { .. } => handle_list_wallets(&paths, &cli.command).await?,
+        Commands::Profiles { .. } => handle_profiles(&paths)?,
+        Commands::UpdatePrices { .. } => update_prices(&paths, &cli.command).await?,
+        Commands::UpdateAllTimeData { .. } => update_all_time_data(&paths, &cli.command).await?,
+        Commands::AthHistory { .. } => handle_ath_history(&paths, &cli.command).await?,
+        Commands::Coins { .. } => handle_coins(&paths, &cli.command).await?,
+        Commands::Wallet { .. } => handle_wallet(&paths, &cli.command).await?,
+        Commands::Holding { .. } => handle_holding(&paths, &cli.command).await?,
+        Commands::Alerts { command: AlertsCommands::Check {} } => alerts::check_alerts(&paths).await?,
+        Commands::Alerts { command: AlertsCommands::Test {} } => alerts::test_sinks(&paths).await?,
+        Commands::Config { .. } => handle_config(&paths, &cli.command)?,
+        Commands::Daemon { .. } => handle_daemon(&paths, &cli.command).await?,
+        Commands::Serve { bind } => api::serve(&paths, *bind).await?,
+        Commands::Metrics { bind, update_every } => metrics::serve_metrics(&paths, *bind, *update_every).await?,
+        Commands::Tui { currency, refresh, no_fetch } => {
+            let options = TuiOptions { currency: currency.clone(), refresh: *refresh, fetch: !no_fetch };
+            tui::run_tui(&paths, &options)?;
+        }
+        Commands::Doctor { offline } => service::doctor::run_doctor(&paths, !offline).await?,
     }
 
     Ok(())
@@ -107,78 +591,244 @@ async fn main() -> Result<()> {
 
 
 
-async fn handle_holdings(command: &Commands) -> Result<()> {
-    if
-        let Commands::Holdings {
-            token,
-            wallet_name,
-            wallet_kind,
-            wallet_address,
-            currency,
-            group_by_token,
-            sort_by,
-            order,
-        } = command
-    {
-        let report_filter: ReportFilter = ReportFilter::new(
-            token.clone(),
-            wallet_name.clone(),
-            wallet_kind.clone(),
-            wallet_address.clone(),
-            currency.clone(),
-            group_by_token.clone(),
-            sort_by.clone(),
-            order.clone()
-        );
+async fn handle_holdings(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::Holdings { filter, output, summary, top_n, allocation_by, ath, all_profiles, watch } = command {
+        let report_filter = filter.to_report_filter();
+        let options = HoldingsOptions { summary: *summary, top_n: *top_n, allocation_by: allocation_by.clone(), ath: *ath, all_profiles: *all_profiles };
+
+        let profiles = if *all_profiles { profile_paths(paths)? } else { vec![paths.clone()] };
+        if let Some(seconds) = watch {
+            if *output != OutputFormat::Table {
+                return Err("--watch needs the table output".into());
+            }
+            return watch_holdings(paths, &profiles, &report_filter, &options, *seconds).await;
+        }
+
+        // Prices of the coins of all profiles are fetched when no prices are stored
+        let profiles_data = read_profiles(&profiles, *ath, *all_profiles)?;
+        let prices = coins::get_coins_prices(paths, &profiles_data.0, &report_filter).await?;
+        let report = holdings_report(profiles_data, &prices, &report_filter, &options);
+        write_report(&report, output)?;
+    }
+
+    Ok(())
+}
+
+struct HoldingsOptions {
+    summary: bool,
+    top_n: usize,
+    allocation_by: Option<AllocationBy>,
+    ath: bool,
+    all_profiles: bool,
+}
+
+fn holdings_report(
+    (coins_data, wallets, ath_data): (CoinsData, WalletsData, Vec<AthInfo>),
+    prices: &[PriceInfo],
+    report_filter: &ReportFilter,
+    options: &HoldingsOptions
+) -> HoldingsReport {
+    if options.ath && !ath_data.iter().any(|info| info.currency == report_filter.currency) {
+        warn!(currency = ?report_filter.currency, "No all time data stored in the currency, run update-all-time-data");
+    }
+
+    debug!(?report_filter, ath = ath_data.len(), "Getting report");
+    let lines = holding_lines(&wallets, prices, &ath_data, report_filter);
+    let summary = (options.summary || options.allocation_by.is_some())
+        .then(|| allocation_summary(&lines, &coins_data, options.top_n, options.allocation_by.as_ref()));
+    let report = HoldingsReport::new(group_report_lines(lines, report_filter), report_filter.currency.clone());
+    match summary {
+        Some(summary) => report.with_summary(summary),
+        None => report,
+    }
+}
+
+// Fetches the prices of the coins of all selected profiles and redraws the report until Ctrl-C. The data dir
+// is locked only during the fetch, when the daemon holds it the prices stored by the daemon are reloaded.
+async fn watch_holdings(paths: &Paths, profiles: &[Paths], report_filter: &ReportFilter, options: &HoldingsOptions, seconds: u64) -> Result<()> {
+    let currencies = read_default_app_config(paths)?.currencies;
+    let mut previous: Option<HoldingsReport> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(seconds.max(1)));
+    // Installed once, so Ctrl-C during the fetch or the redraw stops at the next wait
+    let mut shutdown = daemon::Shutdown::new()?;
+    // The lock failure is reported once, not at every refresh
+    let mut locked = true;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.wait() => return Ok(()),
+        }
+        let profiles_data = read_profiles(profiles, options.ath, options.all_profiles)?;
+        match daemon::lock_data_dir(paths) {
+            Ok(_lock) => {
+                locked = true;
+                tokio::select! {
+                    result = coins::update_prices::update_prices_for_coins(paths, &profiles_data.0, &currencies) => {
+                        if let Err(e) = result {
+                            warn!(error = %e, "Prices update failed");
+                        }
+                    }
+                    _ = shutdown.wait() => return Ok(()),
+                }
+            }
+            Err(e) => {
+                if locked {
+                    warn!(error = %e, "Prices are not fetched, only the stored prices are reloaded");
+                }
+                locked = false;
+            }
+        }
+        let prices = coins::get_latest_prices(paths)?;
+        let mut report = holdings_report(profiles_data, &prices, report_filter, options);
+        if let Some(previous) = previous.as_ref() {
+            report = report.with_changes(previous);
+        }
+
+        // Clears the screen and draws the report from the top left corner
+        print!("\x1b[2J\x1b[H");
+        write_report(&report, &OutputFormat::Table)?;
+        println!("Updated {}, every {} s, Ctrl-C to stop", Local::now().format("%H:%M:%S"), seconds);
+        previous = Some(report);
+    }
+}
+
+// Returns the coins, the wallets and optionally the all time data of the profiles merged together.
+// Wallet names are prefixed with the profile name when requested.
+fn read_profiles(profiles: &[Paths], ath: bool, prefix_wallets: bool) -> Result<(CoinsData, WalletsData, Vec<AthInfo>)> {
+    let mut coins_data = CoinsData { coins: Vec::new() };
+    let mut wallets = WalletsData { wallets: Vec::new(), extra: Default::default() };
+    let mut ath_data = Vec::new();
+    for profile in profiles {
+        let profile_coins = read_default_coins_config(profile)?;
+        let mut profile_wallets = wallets::read_default_wallets_config(profile)?.resolve_aliases(&profile_coins);
+        if prefix_wallets {
+            for wallet in profile_wallets.wallets.iter_mut() {
+                wallet.name = format!("{}/{}", profile.profile_name(), wallet.name);
+            }
+        }
+        if ath {
+            ath_data.append(&mut coins::get_ath_data(profile)?);
+        }
+        coins_data.coins.extend(profile_coins.coins);
+        wallets.wallets.extend(profile_wallets.wallets);
+    }
+    Ok((coins_data, wallets, ath_data))
+}
+
+// Returns the paths of all profiles, the default profile is included when it has wallets
+fn profile_paths(paths: &Paths) -> Result<Vec<Paths>> {
+    let mut profiles = Vec::new();
+    let default_profile = paths.with_profile(None);
+    if default_profile.wallets_config_file().exists() {
+        profiles.push(default_profile);
+    }
+    for profile in paths.list_profiles()? {
+        profiles.push(paths.with_profile(Some(&profile)));
+    }
+    if profiles.is_empty() {
+        return Err("No profiles found".into());
+    }
+    Ok(profiles)
+}
+
+fn handle_profiles(paths: &Paths) -> Result<()> {
+    for profile in profile_paths(paths)? {
+        println!("{}", profile.profile_name());
+    }
+    Ok(())
+}
+
+async fn handle_html_report(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::HtmlReport { filter, out } = command {
+        let report_filter = filter.to_report_filter();
+
+        let coins_data = read_default_coins_config(paths)?;
+        let wallets = wallets::read_default_wallets_config(paths)?.resolve_aliases(&coins_data);
+        let prices = coins::get_coins_prices(paths, &coins_data, &report_filter).await?;
+        let history = coins::get_prices_history(paths)?;
 
-        let wallets = wallets::read_default_wallets_config()?;
-        let prices = coins::get_coins_prices(&report_filter).await?;
+        debug!(?report_filter, snapshots = history.len(), "Getting html report");
+        let lines = holding_lines(&wallets, &prices, &[], &report_filter);
+        let report = HoldingsReport::new(group_report_lines(lines.clone(), &report_filter), report_filter.currency.clone());
+        let value_history = portfolio_value_history(&wallets, &history, &report_filter);
 
-        println!("Getting report for {:?}", report_filter);
-        let report_lines = report_holdings(&wallets, &prices, &report_filter);
-        write_report(&report_lines);
+        write_html_report(out, &report, &lines, &value_history)?;
+        info!(file = %out.display(), "Html report written");
     }
 
     Ok(())
 }
 
-async fn handle_list_wallets(command: &Commands) -> Result<()> {
-    if let Commands::ListWallets { wallet_names: _ } = command {
-        let wallets = wallets::read_default_wallets_config()?;
+async fn handle_list_wallets(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::ListWallets { wallet_names, output } = command {
+        let wallets = wallets::read_default_wallets_config(paths)?;
+        if *wallet_names {
+            for wallet in wallets.wallets.iter() {
+                println!("{}", wallet.name);
+            }
+            return Ok(());
+        }
         let wallet_lines = list_wallets(&wallets);
-        write_wallets_report(&wallet_lines);
+        write_wallets_report(&WalletsReport::new(wallet_lines), output)?;
     }
 
     Ok(())
 }
 
-async fn update_prices(command: &Commands) -> Result<()> {
+async fn update_prices(paths: &Paths, command: &Commands) -> Result<()> {
     if let Commands::UpdatePrices { .. } = command {
-        let mut currencies = Vec::new();
+        // The daemon must not update the prices at the same time
+        let _lock = daemon::lock_data_dir(paths)?;
+        let app_config = read_default_app_config(paths)?;
 
-        currencies.push(Currency::from_str("USD")?);
-        currencies.push(Currency::from_str("EUR")?);
+        coins::update_prices::update_coins_prices(paths, &app_config.currencies).await?;
+        alerts::check_alerts(paths).await?;
+    }
 
-        coins::update_prices::update_coins_prices(&currencies).await?;
+    Ok(())
+}
+
+async fn handle_daemon(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::Daemon { prices_schedule, ath_schedule, run_now } = command {
+        let options = DaemonOptions {
+            prices_schedule: prices_schedule.clone(),
+            ath_schedule: ath_schedule.clone(),
+            run_now: *run_now,
+        };
+        daemon::run_daemon(paths, &options).await?;
     }
 
     Ok(())
 }
 
-async fn update_all_time_data(command: &Commands) -> Result<()> {
-    if let Commands::UpdateAllTimeData { token } = command {
-        println!("Updating all time data for {:?}", token);
+async fn handle_ath_history(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::AthHistory { token, currency, output } = command {
+        let history = coins::get_ath_history(paths, token, currency)?;
+        if history.is_empty() {
+            return Err(
+                format!("No all time data stored for {} in {}, run update-all-time-data first", token, currency.to_uppercase_str()).into()
+            );
+        }
+        write_ath_history(&history, output)?;
+    }
 
-        let mut currencies = Vec::new();
-        currencies.push(Currency::from_str("USD")?);
+    Ok(())
+}
 
-        if token.is_some() {
-            coins::update_ath::update_ath_data_for_token(token.as_ref().unwrap(), &currencies).await?;
-            return Ok(());
+async fn update_all_time_data(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::UpdateAllTimeData { token, restart } = command {
+        info!(?token, "Updating all time data");
+        let _lock = daemon::lock_data_dir(paths)?;
+
+        let app_config = read_default_app_config(paths)?;
+        debug!(currencies = ?app_config.currencies, "Updating currencies");
+
+        if let Some(token) = token {
+            coins::update_ath::update_ath_data_for_token(paths, token, &app_config.currencies).await?;
         }
         else {
-            coins::update_ath::update_ath_data_for_all_tokens(&currencies).await?;
-            return Ok(());
+            coins::update_ath::update_ath_data_for_all_tokens(paths, &app_config.currencies, *restart).await?;
         }
     }
 
@@ -186,12 +836,224 @@ async fn update_all_time_data(command: &Commands) -> Result<()> {
 }
 
 
-async fn update_coins_with_list(command: &Commands) -> Result<()> {
-    if let Commands::UpdateCoinsWithList { } = command {
-        println!("Updating coins data with list from coin gecko");
-        //coin_list::update_coins_with_list_file()?;
+async fn handle_coins(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::Coins { command } = command {
+        match command {
+            CoinsCommands::ResolveIds { all, interactive, pick_top, prefer, list_file, write } => {
+                let options = ResolveIdsOptions {
+                    all: *all,
+                    interactive: *interactive,
+                    pick_top: *pick_top,
+                    preferred: prefer.clone(),
+                    list_file: list_file.clone(),
+                    write: *write,
+                };
+                coin_list::resolve_coin_ids(paths, &options).await?;
+            }
+            CoinsCommands::Sync { list_file, write } => {
+                coin_list::sync_coins(paths, list_file.as_ref(), *write).await?;
+            }
+        }
     }
 
     Ok(())
 }
 
+async fn handle_wallet(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::Wallet { command } = command {
+        // The first wallet of a new profile creates the wallets file
+        let new_file = matches!(command, WalletCommands::Add { .. }) && !paths.wallets_config_file().exists();
+        let mut wallets = match new_file {
+            true => WalletsData { wallets: Vec::new(), extra: Default::default() },
+            false => wallets::read_default_wallets_config(paths)?,
+        };
+        let message = match command {
+            WalletCommands::Add { name, kind, address, tag, chain } => {
+                wallets.add_wallet(WalletDef {
+                    name: name.clone(),
+                    kind: kind.clone(),
+                    address: address.clone(),
+                    tag: tag.clone(),
+                    chain: chain.clone(),
+                    holdings: Vec::new(),
+                    extra: Default::default(),
+                })?;
+                format!("Wallet {} added", name)
+            }
+            WalletCommands::Remove { wallet, force } => {
+                let removed = wallets.remove_wallet(&wallet.name, wallet.address.as_deref(), *force)?;
+                format!("Wallet {} removed with {} holdings", wallet.name, removed.holdings.len())
+            }
+            WalletCommands::Rename { wallet, new_name } => {
+                wallets.rename_wallet(&wallet.name, wallet.address.as_deref(), new_name)?;
+                format!("Wallet {} renamed to {}", wallet.name, new_name)
+            }
+        };
+        wallets::write_default_wallets_config(paths, &wallets)?;
+        println!("{}", message);
+        if new_file {
+            seed_profile_coins(paths)?;
+        }
+    }
+
+    Ok(())
+}
+
+// A new profile gets a copy of the default coins config, so its holdings can be reported right away
+fn seed_profile_coins(paths: &Paths) -> Result<()> {
+    let coins_file = paths.coins_config_file();
+    let default_coins_file = paths.with_profile(None).coins_config_file();
+    if paths.profile.is_none() || coins_file.exists() || !default_coins_file.exists() {
+        return Ok(());
+    }
+    let extension = default_coins_file.extension().unwrap_or_default();
+    let profile_coins_file = coins_file.with_extension(extension);
+    fs::copy(&default_coins_file, &profile_coins_file)
+        .map_err(|e| format!("Cannot copy coins config to {}: {}", profile_coins_file.display(), e))?;
+    println!("Coins config copied from {}", default_coins_file.display());
+    Ok(())
+}
+
+async fn handle_holding(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::Holding { command } = command {
+        let mut wallets = wallets::read_default_wallets_config(paths)?;
+        let message = match command {
+            HoldingCommands::Set { holding, amount } => {
+                let coin = holding.coin.to_uppercase();
+                let amount = wallets.set_holding(&holding.wallet, holding.address.as_deref(), &coin, *amount)?;
+                format!("{} {} in {}", amount, coin, holding.wallet)
+            }
+            HoldingCommands::Add { holding, amount } => {
+                let coin = holding.coin.to_uppercase();
+                let amount = wallets.add_holding(&holding.wallet, holding.address.as_deref(), &coin, *amount)?;
+                format!("{} {} in {}", amount, coin, holding.wallet)
+            }
+            HoldingCommands::Remove { holding } => {
+                let coin = holding.coin.to_uppercase();
+                wallets.remove_holding(&holding.wallet, holding.address.as_deref(), &coin)?;
+                format!("{} removed from {}", coin, holding.wallet)
+            }
+        };
+        wallets::write_default_wallets_config(paths, &wallets)?;
+        println!("{}", message);
+        warn_missing_coins(paths);
+    }
+
+    Ok(())
+}
+
+fn handle_config(paths: &Paths, command: &Commands) -> Result<()> {
+    if let Commands::Config { command } = command {
+        match command {
+            ConfigCommands::Convert { to, file } => {
+                let files = match file {
+                    Some(file) => vec![file.clone()],
+                    None => vec![ConfigFile::Wallets, ConfigFile::Coins, ConfigFile::App, ConfigFile::Alerts],
+                };
+                for file in files {
+                    let file_path = config_file_path(paths, &file);
+                    if !file_path.exists() {
+                        warn!(file = %file_path.display(), "Config not found, skipping");
+                        continue;
+                    }
+                    let new_path = match file {
+                        ConfigFile::Wallets => convert_config::<WalletsData>(&file_path, *to, wallets::WALLETS_MIGRATIONS)?,
+                        ConfigFile::Coins => convert_config::<CoinsData>(&file_path, *to, config::coins::COINS_MIGRATIONS)?,
+                        ConfigFile::App => convert_config::<AppConfig>(&file_path, *to, config::app::APP_MIGRATIONS)?,
+                        ConfigFile::Alerts => convert_config::<AlertsConfig>(&file_path, *to, config::alerts::ALERTS_MIGRATIONS)?,
+                    };
+                    println!("{} converted to {}", file_path.display(), new_path.display());
+                }
+            }
+            ConfigCommands::Encrypt { file } => {
+                let file_path = config_file_path(paths, file);
+                let new_path = crypto::encrypt_config_file(&file_path)?;
+                println!("{} encrypted to {}", file_path.display(), new_path.display());
+            }
+            ConfigCommands::Decrypt { file } => {
+                let file_path = config_file_path(paths, file);
+                let new_path = crypto::decrypt_config_file(&file_path)?;
+                println!("{} decrypted to {}", file_path.display(), new_path.display());
+            }
+            ConfigCommands::Rekey { file } => {
+                let file_path = config_file_path(paths, file);
+                crypto::rekey_config_file(&file_path)?;
+                println!("{} encrypted with the new passphrase", file_path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn config_file_path(paths: &Paths, file: &ConfigFile) -> PathBuf {
+    match file {
+        ConfigFile::Wallets => paths.wallets_config_file(),
+        ConfigFile::Coins => paths.coins_config_file(),
+        ConfigFile::App => paths.app_config_file(),
+        ConfigFile::Alerts => paths.alerts_config_file(),
+    }
+}
+
+// Warns about holdings which have no coin in the coins config, as their prices are never updated.
+// Configs which cannot be read are reported by the commands.
+fn warn_missing_coins(paths: &Paths) {
+    let (Ok(coins_data), Ok(wallets)) = (read_default_coins_config(paths), wallets::read_default_wallets_config(paths)) else {
+        return;
+    };
+    let missing = wallets.missing_coins(&coins_data);
+    if !missing.is_empty() {
+        warn!(?missing, "Coins from wallets are not in the coins config, run coins sync");
+    }
+}
+
+// Parses CODE=id pairs of the preferred ids
+fn parse_preferred_id(val: &str) -> core::result::Result<(String, String), String> {
+    let (code, id) = val
+        .split_once('=')
+        .ok_or_else(|| format!("Expected CODE=id, got {}", val))?;
+    Ok((code.trim().to_string(), id.trim().to_string()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_profile(dir: &std::path::Path, wallet: &str, coin: &str) {
+        fs::create_dir_all(dir).unwrap();
+        let wallets = format!(
+            r#"{{ "wallets": [{{ "name": "{}", "kind": "Ledger", "address": "a", "holdings": [{{ "coin": "{}", "amount": 1.0 }}] }}] }}"#,
+            wallet,
+            coin
+        );
+        fs::write(dir.join("wallets.json"), wallets).unwrap();
+        fs::write(dir.join("coins.json"), format!(r#"{{ "coins": [{{ "code": "{}" }}] }}"#, coin)).unwrap();
+    }
+
+    #[test]
+    fn test_profiles() {
+        let dir = tempdir().unwrap();
+        let paths = Paths { config_dir: dir.path().join("conf"), data_dir: dir.path().join("data"), profile: None };
+        assert!(profile_paths(&paths).is_err());
+
+        write_profile(&paths.config_dir.join("profiles/treasury"), "Cold", "ETH");
+        let names = |profiles: Vec<Paths>| profiles.iter().map(|profile| profile.profile_name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(profile_paths(&paths).unwrap()), vec!["treasury"]);
+
+        write_profile(&paths.config_dir, "Ledger", "BTC");
+        let profiles = profile_paths(&paths.with_profile(Some("treasury"))).unwrap();
+        assert_eq!(names(profile_paths(&paths).unwrap()), vec!["default", "treasury"]);
+
+        let (coins_data, wallets, ath_data) = read_profiles(&profiles, true, true).unwrap();
+        let wallet_names: Vec<&str> = wallets.wallets.iter().map(|wallet| wallet.name.as_str()).collect();
+        assert_eq!(wallet_names, vec!["default/Ledger", "treasury/Cold"]);
+        let codes: Vec<&str> = coins_data.coins.iter().map(|coin| coin.code.as_str()).collect();
+        assert_eq!(codes, vec!["BTC", "ETH"]);
+        assert!(ath_data.is_empty());
+
+        let (_, wallets, _) = read_profiles(&profiles[1..], false, false).unwrap();
+        assert_eq!(wallets.wallets[0].name, "Cold");
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeSet;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::sync::{ Arc, LazyLock, Mutex };
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{ header::CONTENT_TYPE, StatusCode };
+use axum::response::{ IntoResponse, Response };
+use axum::routing::get;
+use axum::Router;
+use prometheus::{ Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder, TEXT_FORMAT };
+use tokio::net::TcpListener;
+use tracing::{ info, warn };
+
+use crate::coins;
+use crate::config::app::read_default_app_config;
+use crate::config::coins::read_default_coins_config;
+use crate::config::paths::Paths;
+use crate::config::wallets::{ read_default_wallets_config, WalletsData };
+use crate::daemon::lock_data_dir;
+use crate::model::{ Currency, PriceInfo };
+use crate::Result;
+
+// Requests to the providers made by this process, recorded by every command
+struct ProviderMetrics {
+    requests: IntCounterVec,
+    duration: HistogramVec,
+}
+
+static PROVIDER_METRICS: LazyLock<ProviderMetrics> = LazyLock::new(|| ProviderMetrics {
+    requests: IntCounterVec::new(
+        Opts::new("crpt_provider_requests_total", "Requests to the price and all time data providers"),
+        &["provider", "status"]
+    ).expect("valid metric"),
+    duration: HistogramVec::new(
+        HistogramOpts::new("crpt_provider_request_duration_seconds", "Duration of the requests to the providers"),
+        &["provider"]
+    ).expect("valid metric"),
+});
+
+pub fn record_provider_request(provider: &str, success: bool, duration: Duration) {
+    let status = if success { "success" } else { "failure" };
+    PROVIDER_METRICS.requests.with_label_values(&[provider, status]).inc();
+    PROVIDER_METRICS.duration.with_label_values(&[provider]).observe(duration.as_secs_f64());
+}
+
+// Gauges of the portfolio, computed from the stored prices at every scrape
+struct PortfolioMetrics {
+    registry: Registry,
+    portfolio_value: GaugeVec,
+    token_value: GaugeVec,
+    wallet_value: GaugeVec,
+    token_price: GaugeVec,
+    token_change_24h: GaugeVec,
+}
+
+impl PortfolioMetrics {
+    fn new() -> Result<Self> {
+        let gauge = |name: &str, help: &str, labels: &[&str]| GaugeVec::new(Opts::new(name, help), labels);
+        let metrics = PortfolioMetrics {
+            registry: Registry::new(),
+            portfolio_value: gauge("crpt_portfolio_value", "Value of all holdings", &["currency"])?,
+            token_value: gauge("crpt_token_value", "Value of the token in all wallets", &["token", "currency"])?,
+            wallet_value: gauge("crpt_wallet_value", "Value of the holdings in the wallet", &["wallet", "kind", "currency"])?,
+            token_price: gauge("crpt_token_price", "Latest stored price of the token", &["token", "currency"])?,
+            token_change_24h: gauge("crpt_token_change_24h_percent", "Price change of the token in 24 hours", &["token", "currency"])?,
+        };
+        for gauge in metrics.gauges() {
+            metrics.registry.register(Box::new(gauge.clone()))?;
+        }
+        metrics.registry.register(Box::new(PROVIDER_METRICS.requests.clone()))?;
+        metrics.registry.register(Box::new(PROVIDER_METRICS.duration.clone()))?;
+        Ok(metrics)
+    }
+
+    fn gauges(&self) -> [&GaugeVec; 5] {
+        [&self.portfolio_value, &self.token_value, &self.wallet_value, &self.token_price, &self.token_change_24h]
+    }
+
+    // Replaces the values, so removed wallets and tokens disappear. Holdings without price are not counted.
+    fn update(&self, wallets: &WalletsData, prices: &[PriceInfo], currencies: &[Currency]) {
+        for gauge in self.gauges() {
+            gauge.reset();
+        }
+        let tokens: BTreeSet<&str> = wallets.wallets
+            .iter()
+            .flat_map(|wallet| wallet.holdings.iter().map(|holding| holding.coin.as_str()))
+            .collect();
+
+        for currency in currencies {
+            let currency_label = currency.to_uppercase_str();
+            let price = |coin: &str| prices.iter().find(|price| price.coin == coin && price.currency == *currency);
+
+            let portfolio_value = self.portfolio_value.with_label_values(&[currency_label]);
+            portfolio_value.set(0.0);
+            for wallet in wallets.wallets.iter() {
+                let wallet_value = self.wallet_value.with_label_values(&[&wallet.name, &wallet.kind, currency_label]);
+                for holding in wallet.holdings.iter() {
+                    let Some(price) = price(&holding.coin) else {
+                        continue;
+                    };
+                    let value = holding.amount * price.value;
+                    wallet_value.add(value);
+                    self.token_value.with_label_values(&[&holding.coin, currency_label]).add(value);
+                    portfolio_value.add(value);
+                }
+            }
+            for token in tokens.iter() {
+                if let Some(price) = price(token) {
+                    self.token_price.with_label_values(&[*token, currency_label]).set(price.value);
+                    self.token_change_24h.with_label_values(&[*token, currency_label]).set(price.change_24h);
+                }
+            }
+        }
+    }
+
+    fn render(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+struct MetricsState {
+    paths: Paths,
+    // Scrapes are serialized, so one scrape does not see the gauges reset by another
+    metrics: Mutex<PortfolioMetrics>,
+}
+
+// Serves /metrics until Ctrl-C. With update_every the prices are updated by this process, so the provider
+// metrics are filled. The data dir is locked then like by the daemon.
+pub async fn serve_metrics(paths: &Paths, bind: SocketAddr, update_every: Option<u64>) -> Result<()> {
+    let _lock = update_every.map(|_| lock_data_dir(paths)).transpose()?;
+    let listener = TcpListener::bind(bind).await.map_err(|e| format!("Cannot listen on {}: {}", bind, e))?;
+    info!(address = %listener.local_addr()?, ?update_every, "Serving metrics");
+
+    let state = Arc::new(MetricsState { paths: paths.clone(), metrics: Mutex::new(PortfolioMetrics::new()?) });
+    let app = Router::new().route("/metrics", get(metrics)).with_state(state);
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+            info!("Stopping metrics");
+        })
+        .into_future();
+
+    match update_every {
+        Some(seconds) => {
+            tokio::select! {
+                result = server => result?,
+                _ = update_prices_every(paths, seconds) => {}
+            }
+        }
+        None => server.await?,
+    }
+    Ok(())
+}
+
+// Updates the prices at the start and then every given seconds, failed updates are retried at the next time
+async fn update_prices_every(paths: &Paths, seconds: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(seconds.max(1)));
+    loop {
+        interval.tick().await;
+        let result = match read_default_app_config(paths) {
+            Ok(app_config) => coins::update_prices::update_coins_prices(paths, &app_config.currencies).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(error = %e, "Prices update failed");
+        }
+    }
+}
+
+async fn metrics(State(state): State<Arc<MetricsState>>) -> Response {
+    match render_metrics(&state) {
+        Ok(text) => ([(CONTENT_TYPE, TEXT_FORMAT)], text).into_response(),
+        Err(e) => {
+            warn!(error = %e, "Cannot render metrics");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+fn render_metrics(state: &MetricsState) -> Result<String> {
+    let paths = &state.paths;
+    let app_config = read_default_app_config(paths)?;
+    let coins_data = read_default_coins_config(paths)?;
+    let wallets = read_default_wallets_config(paths)?.resolve_aliases(&coins_data);
+    let prices = coins::get_latest_prices(paths)?;
+
+    let metrics = state.metrics.lock().map_err(|_| "Metrics lock failed")?;
+    metrics.update(&wallets, &prices, &app_config.currencies);
+    metrics.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(coin: &str, currency: Currency, value: f64) -> PriceInfo {
+        PriceInfo { coin: coin.to_string(), currency, value, market_cap: 0.0, change_24h: -2.5 }
+    }
+
+    #[test]
+    fn test_portfolio_metrics() {
+        let wallets: WalletsData = serde_json::from_value(serde_json::json!({
+            "wallets": [
+                { "name": "Ledger", "kind": "Ledger", "address": "a", "holdings": [
+                    { "coin": "BTC", "amount": 2.0 },
+                    { "coin": "ETH", "amount": 1.0 },
+                    { "coin": "DUST", "amount": 100.0 }
+                ] },
+                { "name": "MetaMask", "kind": "MetaMask", "address": "b", "holdings": [{ "coin": "ETH", "amount": 3.0 }] }
+            ]
+        })).unwrap();
+        let prices = vec![price("BTC", Currency::USD, 100000.0), price("ETH", Currency::USD, 2000.0), price("ETH", Currency::EUR, 1900.0)];
+
+        let metrics = PortfolioMetrics::new().unwrap();
+        metrics.update(&wallets, &prices, &[Currency::USD, Currency::EUR]);
+        record_provider_request("coingecko", true, Duration::from_millis(120));
+        let text = metrics.render().unwrap();
+
+        assert!(text.contains("crpt_portfolio_value{currency=\"USD\"} 208000\n"), "{}", text);
+        assert!(text.contains("crpt_portfolio_value{currency=\"EUR\"} 7600\n"), "{}", text);
+        assert!(text.contains("crpt_wallet_value{currency=\"USD\",kind=\"Ledger\",wallet=\"Ledger\"} 202000\n"), "{}", text);
+        assert!(text.contains("crpt_token_value{currency=\"USD\",token=\"ETH\"} 8000\n"), "{}", text);
+        assert!(text.contains("crpt_token_change_24h_percent{currency=\"EUR\",token=\"ETH\"} -2.5\n"), "{}", text);
+        assert!(!text.contains("token=\"DUST\""), "{}", text);
+        assert!(text.contains("crpt_provider_requests_total{provider=\"coingecko\",status=\"success\"}"), "{}", text);
+    }
+}
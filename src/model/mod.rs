@@ -5,9 +5,11 @@ use clap::ValueEnum;
 
 //use core::Result;
 
-#[derive(Debug, PartialEq, Clone, ValueEnum, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone, Default, ValueEnum, Serialize, Deserialize)]
 pub enum Currency {
     EUR,
+    #[default]
     USD,
 }
 
@@ -18,7 +20,7 @@ impl FromStr for Currency {
         match val {
             "eur" | "EUR" => Ok(Currency::EUR),
             "usd" | "USD" => Ok(Currency::USD),
-            _ => Err(format!("Cannot convert {} into currency", val).into()),
+            _ => Err(format!("Cannot convert {} into currency", val)),
         }
     }
 }
@@ -35,9 +37,9 @@ impl Currency {
         self.as_str_pair().0
     }
 
-//    pub fn to_lowercase_str(&self) -> &'static str {
-//        self.as_str_pair().1
-//    }
+    pub fn to_lowercase_str(&self) -> &'static str {
+        self.as_str_pair().1
+    }
 
 }
 
@@ -48,7 +50,10 @@ pub struct PriceInfo {
     pub coin: String,
     pub currency: Currency,
     pub value: f64,
+    // Older price files do not contain market data
+    #[serde(default)]
     pub market_cap: f64,
+    #[serde(default)]
     pub change_24h: f64,
 }
 
@@ -56,6 +61,9 @@ pub struct PriceInfo {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AthInfo {
     pub coin : String,
+    // Older ATH files were stored only in USD
+    #[serde(default)]
+    pub currency: Currency,
     pub ath: f64,
     pub ath_date: String,
     pub ath_change_percentage: f64,
@@ -66,13 +74,15 @@ pub struct AthInfo {
 }
 
 
-#[derive(Debug, ValueEnum, Clone)]
+#[derive(Debug, ValueEnum, Clone, PartialEq)]
 pub enum ReportSortBy {
     Token,
     Amount,
     Value,
     MktCap,
     Change24h,
+    // Distance from ATH, useful for drawdown view with --ath
+    FromAth,
 }
 
 #[derive(Debug, ValueEnum, Clone, PartialEq)]
@@ -82,3 +92,27 @@ pub enum ReportOrder {
 }
 
 
+
+#[derive(Debug, ValueEnum, Clone, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Markdown,
+}
+
+#[derive(Debug, ValueEnum, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AllocationBy {
+    Kind,
+    Category,
+}
+
+#[derive(Debug, ValueEnum, Clone, PartialEq)]
+pub enum GroupBy {
+    Token,
+    Wallet,
+    Kind,
+    Tag,
+    Chain,
+}
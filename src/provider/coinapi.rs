@@ -2,6 +2,7 @@ use async_trait::async_trait;
 
 use crate::client::coinapi::CoinAPIClient;
 use crate::client::CurrentPriceApi;
+use crate::config::coins::provider_symbol;
 use crate::Result;
 use crate::model::{Currency, PriceInfo};
 
@@ -13,8 +14,12 @@ pub struct CoinAPIPriceProvider {
 
 #[async_trait]
 impl PriceProvider for CoinAPIPriceProvider {
-    async fn get_price(&self, symbol: &str, _additional_data: &Option<std::collections::HashMap<String,String>>, _currencies: &Vec<Currency>) -> Result<Vec<PriceInfo>> {
+    async fn get_price(&self, symbol: &str, additional_data: &Option<std::collections::HashMap<String,String>>, _currencies: &[Currency]) -> Result<Vec<PriceInfo>> {
         let client  = CoinAPIClient::new();
-        Ok(client.get_prices(symbol).await?)
+        let mut prices = client.get_prices(provider_symbol(symbol, additional_data)).await?;
+        for price in prices.iter_mut() {
+            price.coin = symbol.to_string();
+        }
+        Ok(prices)
     }
-}
\ No newline at end of file
+}
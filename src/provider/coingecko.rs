@@ -1,6 +1,13 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
+use tracing::{ debug, warn };
 
-use crate::{ client::coingecko::CoinGeckoClient, model::{AthInfo, Currency, PriceInfo} };
+use crate::{
+    client::coingecko::{ CoinGeckoClient, CoinMarket, MARKETS_PAGE_SIZE },
+    config::coins::CoinDef,
+    model::{AthInfo, Currency},
+};
 
 use crate::Result;
 
@@ -13,37 +20,96 @@ impl AthProvider for CoinGeckoAthProvider {
     async fn get_ath(
         &self,
         symbol: &str,
-        additional_data: Option<&std::collections::HashMap<String, String>>,
-        currencies: &Vec<Currency>
+        additional_data: Option<&HashMap<String, String>>,
+        currencies: &[Currency]
     ) -> Result<Vec<AthInfo>> {
         let client = CoinGeckoClient::new();
 
-        let token_id: String = additional_data.map_or_else(
-            || String::from(symbol),
-            |map| map.get("token_id").map_or(String::from(symbol), |val| val.clone())
-        );
+        let token_id = get_token_id(symbol, additional_data);
+
+        let mut result = Vec::new();
+
+        // Markets endpoint returns the data only in one currency
+        for currency in currencies_or_default(currencies).iter() {
+            let market_data = client.get_coins_markets(vec![token_id.clone()], currency.to_lowercase_str()).await?;
+            for record in market_data.iter() {
+                result.push(ath_info(symbol, currency, record));
+            }
+        }
+
+        Ok(result)
+    }
 
-        let mut token_ids = vec![token_id];
-        //:TODO - currency
-        let currency = "usd";    
+    // Gets the data with one request per page of coins and currency
+    async fn get_aths(&self, coins_definitions: &[CoinDef], currencies: &[Currency]) -> Result<Vec<AthInfo>> {
+        let client = CoinGeckoClient::new();
 
-        let market_data = client.get_coins_markets(token_ids, &currency).await?;
+        // More coins can use the same token id
+        let mut coins_by_id: HashMap<String, Vec<&str>> = HashMap::new();
+        for coin_def in coins_definitions {
+            let token_id = get_token_id(&coin_def.code, coin_def.ath_provider_data.as_ref());
+            coins_by_id.entry(token_id).or_default().push(&coin_def.code);
+        }
+        let mut token_ids: Vec<String> = coins_by_id.keys().cloned().collect();
+        token_ids.sort();
 
         let mut result = Vec::new();
+        for currency in currencies_or_default(currencies).iter() {
+            for page in token_ids.chunks(MARKETS_PAGE_SIZE) {
+                debug!(currency = currency.to_uppercase_str(), coins = page.len(), "Getting markets page");
+                let market_data = client.get_coins_markets(page.to_vec(), currency.to_lowercase_str()).await?;
+                result.append(&mut ath_infos_for_markets(&coins_by_id, currency, &market_data));
+            }
+        }
 
-        for record in market_data.iter() {
-            let ath_info = AthInfo {
-                coin: symbol.to_string(),
-                ath: record.ath,
-                ath_change_percentage: record.ath_change_percentage,
-                ath_date: record.ath_date.clone(),
-                atl: record.atl,
-                atl_change_percentage: record.atl_change_percentage,    
-                atl_date: record.atl_date.clone(),
-            };
-            result.push(ath_info);
+        for (token_id, coins) in coins_by_id.iter() {
+            if !result.iter().any(|ath_info| coins.contains(&ath_info.coin.as_str())) {
+                warn!(token_id, ?coins, "No market data for token id");
+            }
         }
 
         Ok(result)
     }
 }
+
+fn get_token_id(symbol: &str, additional_data: Option<&HashMap<String, String>>) -> String {
+    additional_data.map_or_else(
+        || String::from(symbol),
+        |map| map.get("token_id").map_or(String::from(symbol), |val| val.clone())
+    )
+}
+
+fn currencies_or_default(currencies: &[Currency]) -> Vec<Currency> {
+    if currencies.is_empty() { vec![Currency::default()] } else { currencies.to_vec() }
+}
+
+// Maps the market records back to the coins by token id
+fn ath_infos_for_markets(
+    coins_by_id: &HashMap<String, Vec<&str>>,
+    currency: &Currency,
+    market_data: &[CoinMarket]
+) -> Vec<AthInfo> {
+    market_data
+        .iter()
+        .flat_map(|record| {
+            coins_by_id
+                .get(&record.id)
+                .into_iter()
+                .flatten()
+                .map(|coin| ath_info(coin, currency, record))
+        })
+        .collect()
+}
+
+fn ath_info(symbol: &str, currency: &Currency, record: &CoinMarket) -> AthInfo {
+    AthInfo {
+        coin: symbol.to_string(),
+        currency: currency.clone(),
+        ath: record.ath,
+        ath_change_percentage: record.ath_change_percentage,
+        ath_date: record.ath_date.clone(),
+        atl: record.atl,
+        atl_change_percentage: record.atl_change_percentage,
+        atl_date: record.atl_date.clone(),
+    }
+}
@@ -2,12 +2,14 @@ use async_trait::async_trait;
 
 use crate::{
     client::cryptocompare::{ CryptoCompareClient, PriceMultiFullResponse },
-    config::coins::CoinDef,
+    config::coins::{ provider_symbol, CoinDef },
     model::{ Currency, PriceInfo },
 };
 use crate::Result;
 
+use std::collections::HashMap;
 use std::str::FromStr;
+use tracing::{ debug, warn };
 
 use super::PriceProvider;
 
@@ -21,53 +23,54 @@ impl PriceProvider for CryptoComparePriceProvider {
     async fn get_price(
         &self,
         symbol: &str,
-        _additional_data: &Option<std::collections::HashMap<String, String>>,
-        currencies: &Vec<Currency>
+        additional_data: &Option<HashMap<String, String>>,
+        currencies: &[Currency]
     ) -> Result<Vec<PriceInfo>> {
         let client = CryptoCompareClient::new();
-        let mut token_ids = Vec::new();
-        token_ids.push(symbol);
+        let provider_symbol = provider_symbol(symbol, additional_data);
+        let token_ids = vec![provider_symbol];
 
-        let currencies_str: Vec<&str> = convert_currencies(&currencies);
+        let currencies_str: Vec<&str> = convert_currencies(currencies);
         let resp = client.get_coin_info(&token_ids, &currencies_str).await?;
-        convert_response(&resp)
+        let codes = HashMap::from([(provider_symbol, symbol)]);
+        convert_response(&resp, &codes)
     }
 
     async fn get_prices(
         &self,
-        coins_definitions: &Vec<CoinDef>,
-        currencies: &Vec<Currency>
+        coins_definitions: &[CoinDef],
+        currencies: &[Currency]
     ) -> Result<Vec<PriceInfo>> {
 
-        println!("CryptoCompare: get_prices:");
-        println!("Coins: {:?}", coins_definitions.len());
-        println!("Currencies: {:?}", currencies);
+        debug!(coins = coins_definitions.len(), ?currencies, "CryptoCompare: get_prices");
 
         let mut result = Vec::new();
 
         let client = CryptoCompareClient::new();
 
-        let currencies_str: Vec<&str> = convert_currencies(&currencies);
+        let currencies_str: Vec<&str> = convert_currencies(currencies);
 
-        let all_coins = split_coins_inputs(&coins_definitions);
+        // Response contains the provider symbols, they are converted back to the codes
+        let codes: HashMap<&str, &str> = coins_definitions
+            .iter()
+            .map(|coin_def| (coin_def.price_provider_symbol(), coin_def.code.as_str()))
+            .collect();
+
+        let all_coins = split_coins_inputs(coins_definitions);
         if all_coins.is_empty() {
-            println!("No input coins found");
+            warn!("No input coins found");
             return Ok(Vec::new());
         }
 
         for group in all_coins {
-            println!("Making request for {:?}", group);
+            debug!(?group, "Making request");
             match client.get_coin_info(&group, &currencies_str).await {
                 Ok(res) => {
-                    let mut res: Vec<PriceInfo> = convert_response(&res)?;
+                    let mut res: Vec<PriceInfo> = convert_response(&res, &codes)?;
                     result.append(&mut res);
                 }
                 Err(err) => {
-                    println!(
-                        "Problem getting price data for: {:?}: {:?}",
-                        group,
-                        err
-                    );
+                    warn!(?group, error = %err, "Problem getting price data");
                 }
             }
         }
@@ -75,7 +78,7 @@ impl PriceProvider for CryptoComparePriceProvider {
     }
 }
 
-fn split_coins_inputs(coins_definitions: &Vec<CoinDef>) -> Vec<Vec<&str>> {
+fn split_coins_inputs(coins_definitions: &[CoinDef]) -> Vec<Vec<&str>> {
 
     let mut result = Vec::new();
     if coins_definitions.is_empty() {
@@ -87,14 +90,15 @@ fn split_coins_inputs(coins_definitions: &Vec<CoinDef>) -> Vec<Vec<&str>> {
     
     for coin_def in coins_definitions {
         
-        let len_with_coma = &coin_def.code.len() + 1;
+        let symbol = coin_def.price_provider_symbol();
+        let len_with_coma = symbol.len() + 1;
         if length + len_with_coma >= MAX_SYMBOLS_LEN - SYMBOLS_TOLLERANCE {
             result.push(current_group);
             current_group = Vec::new();
             length = 0;
             
         }
-        current_group.push(&coin_def.code);
+        current_group.push(symbol);
         length += len_with_coma;
     }
 
@@ -105,11 +109,11 @@ fn split_coins_inputs(coins_definitions: &Vec<CoinDef>) -> Vec<Vec<&str>> {
     result
 }
 
-fn convert_response(response: &PriceMultiFullResponse) -> Result<Vec<PriceInfo>> {
+fn convert_response(response: &PriceMultiFullResponse, codes: &HashMap<&str, &str>) -> Result<Vec<PriceInfo>> {
     let mut result_prices = Vec::new();
 
     for (key, val) in response.raw.iter() {
-        let symbol = key;
+        let symbol = codes.get(key.as_str()).copied().unwrap_or(key);
         for (curr, level2) in val {
             let price_info = PriceInfo {
                 coin: symbol.to_string(),
@@ -125,7 +129,7 @@ fn convert_response(response: &PriceMultiFullResponse) -> Result<Vec<PriceInfo>>
     Ok(result_prices)
 }
 
-fn convert_currencies(currencies: &Vec<Currency>) -> Vec<&str> {
+fn convert_currencies(currencies: &[Currency]) -> Vec<&str> {
     let currencies_str: Vec<&str> = currencies
     .iter()
     .map(|cur| cur.to_uppercase_str())
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use coinapi::CoinAPIPriceProvider;
 use coingecko::CoinGeckoAthProvider;
 use cryptocompare::CryptoComparePriceProvider;
+use tracing::{ debug, warn };
 
 use crate::config::coins::{AthProviderEnum, CoinDef, PriceProviderEnum};
 use crate::model::{AthInfo, Currency, PriceInfo};
@@ -15,17 +16,19 @@ mod coingecko;
 
 #[async_trait]
 pub trait PriceProvider: Send + Sync  {
-    async fn get_price(&self, symbol: &str, additional_data: &Option<HashMap<String,String>>, currencies: &Vec<Currency>) -> Result<Vec<PriceInfo>>;  
+    async fn get_price(&self, symbol: &str, additional_data: &Option<HashMap<String,String>>, currencies: &[Currency]) -> Result<Vec<PriceInfo>>;  
 
-    async fn get_prices(&self, coins_definitions: &Vec<CoinDef>, currencies: &Vec<Currency>) -> Result<Vec<PriceInfo>> {
-        println!("Default get prices");
-        println!("coins: {:?}", coins_definitions.len());
+    async fn get_prices(&self, coins_definitions: &[CoinDef], currencies: &[Currency]) -> Result<Vec<PriceInfo>> {
+        debug!(coins = coins_definitions.len(), "Default get prices");
         let mut result = Vec::new();
         for coin_def in coins_definitions {
             //:TODO - skusit prepisat stream based
-            let Ok(mut res) = self.get_price(&coin_def.code, &coin_def.price_provider_data, &currencies).await else {
-                println!("Problem getting price data for: {}", coin_def.code);
-                continue;
+            let mut res = match self.get_price(&coin_def.code, &coin_def.price_provider_data, currencies).await {
+                Ok(res) => res,
+                Err(e) => {
+                    warn!(coin = %coin_def.code, error = %e, "Problem getting price data");
+                    continue;
+                }
             };
             result.append(&mut res);
         }
@@ -35,8 +38,22 @@ pub trait PriceProvider: Send + Sync  {
 }
 
 #[async_trait]
-pub trait AthProvider {
-    async fn get_ath(&self, symbol: &str, additional_data: Option<&HashMap<String,String>>, currencies: &Vec<Currency>) -> Result<Vec<AthInfo>>;
+pub trait AthProvider: Send + Sync {
+    async fn get_ath(&self, symbol: &str, additional_data: Option<&HashMap<String,String>>, currencies: &[Currency]) -> Result<Vec<AthInfo>>;
+
+    // Returns the data for more coins at once. Providers which support batch requests should override it,
+    // the default calls get_ath for each coin and skips the coins with errors.
+    async fn get_aths(&self, coins_definitions: &[CoinDef], currencies: &[Currency]) -> Result<Vec<AthInfo>> {
+        debug!(coins = coins_definitions.len(), "Default get ATHs");
+        let mut result = Vec::new();
+        for coin_def in coins_definitions {
+            match self.get_ath(&coin_def.code, coin_def.ath_provider_data.as_ref(), currencies).await {
+                Ok(mut res) => result.append(&mut res),
+                Err(e) => warn!(coin = %coin_def.code, error = %e, "Problem getting ATH data"),
+            }
+        }
+        Ok(result)
+    }
 }
 
 pub fn get_price_provider(price_provider_id: &PriceProviderEnum) -> Box<dyn PriceProvider> {
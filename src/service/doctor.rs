@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{ self, Write };
+use std::path::Path;
+
+use colored::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::client;
+use crate::config::{ self, Migration };
+use crate::config::app::{ AppConfig, APP_MIGRATIONS };
+use crate::config::coins::{ AthProviderEnum, CoinsData, PriceProviderEnum, COINS_MIGRATIONS };
+use crate::config::paths::Paths;
+use crate::config::wallets::{ WalletsData, WALLETS_MIGRATIONS };
+use crate::model::Currency;
+use crate::provider::{ get_ath_provider, get_price_provider };
+use crate::Result;
+
+#[derive(Debug, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub status: CheckStatus,
+    pub name: String,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(status: CheckStatus, name: &str, detail: impl Into<String>) -> Self {
+        Check {
+            status,
+            name: name.to_string(),
+            detail: detail.into(),
+        }
+    }
+}
+
+// Runs all checks and prints the checklist. Returns error when any check failed.
+// Providers are probed with one cheap request each, unless probing is disabled.
+pub async fn run_doctor(paths: &Paths, probe: bool) -> Result<()> {
+    let mut checks = Vec::new();
+
+    let wallets = read_config::<WalletsData>(&paths.wallets_config_file(), WALLETS_MIGRATIONS, &mut checks);
+    let coins = read_config::<CoinsData>(&paths.coins_config_file(), COINS_MIGRATIONS, &mut checks);
+    if coins.is_none() {
+        if let Ok(value) = config::read_config_value(&paths.coins_config_file()) {
+            checks.append(&mut check_coin_providers(&value));
+        }
+    }
+    let app_config_file = paths.app_config_file();
+    if app_config_file.exists() {
+        if read_config::<AppConfig>(&app_config_file, APP_MIGRATIONS, &mut checks).is_some() {
+            if let Ok(value) = config::read_config_value(&app_config_file) {
+                checks.append(&mut check_app_providers(&value));
+            }
+        }
+    } else {
+        let name = app_config_file.display().to_string();
+        checks.push(Check::new(CheckStatus::Pass, &name, "not present, defaults are used"));
+    }
+
+    if let Some(wallets) = wallets.as_ref() {
+        checks.append(&mut check_wallets(wallets));
+    }
+    if let Some(coins) = coins.as_ref() {
+        checks.append(&mut check_coins(coins, wallets.as_ref()));
+        checks.append(&mut check_api_keys(coins));
+    }
+    checks.push(check_data_dir(&paths.data_dir));
+
+    if probe {
+        checks.append(&mut probe_providers().await);
+    }
+
+    let mut out = io::stdout().lock();
+    write_checklist(&mut out, &checks)?;
+
+    let failed = checks.iter().filter(|check| check.status == CheckStatus::Fail).count();
+    if failed > 0 {
+        return Err(format!("{} checks failed", failed).into());
+    }
+    Ok(())
+}
+
+fn read_config<T>(file_path: &Path, migrations: &[Migration], checks: &mut Vec<Check>) -> Option<T>
+where T: DeserializeOwned {
+    let name = file_path.display().to_string();
+    match config::read_config(file_path, migrations) {
+        Ok(config) => {
+            checks.push(Check::new(CheckStatus::Pass, &name, "valid"));
+            Some(config)
+        }
+        Err(e) => {
+            checks.push(Check::new(CheckStatus::Fail, &name, e.to_string()));
+            None
+        }
+    }
+}
+
+// Names of the providers as they are written in the configs
+fn provider_names<T: Serialize>(providers: &[T]) -> Vec<String> {
+    providers
+        .iter()
+        .filter_map(|provider| serde_json::to_value(provider).ok())
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .collect()
+}
+
+// Finds the coins with unknown provider names, used when the coins config cannot be read
+pub fn check_coin_providers(value: &Value) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let price_providers = provider_names(&PriceProviderEnum::ALL);
+    let ath_providers = provider_names(&AthProviderEnum::ALL);
+    for coin in value["coins"].as_array().into_iter().flatten() {
+        let code = coin["code"].as_str().unwrap_or("?");
+        for (field, known) in [("priceProvider", &price_providers), ("athProvider", &ath_providers)] {
+            if let Some(provider) = coin[field].as_str() {
+                if !known.iter().any(|name| name == provider) {
+                    checks.push(Check::new(
+                        CheckStatus::Fail,
+                        &format!("coin {}", code),
+                        format!("unknown {} {}, expected one of {}", field, provider, known.join(", "))
+                    ));
+                }
+            }
+        }
+    }
+    checks
+}
+
+// Providers of the app config have to be known, their apiKey is the name of the env variable with the key
+pub fn check_app_providers(value: &Value) -> Vec<Check> {
+    let known = provider_names(&PriceProviderEnum::ALL);
+    let mut checks = Vec::new();
+    for provider in value["providers"].as_array().into_iter().flatten() {
+        let name = provider["name"].as_str().unwrap_or_default();
+        let check_name = format!("provider {}", name);
+        if !known.iter().any(|known_name| known_name.eq_ignore_ascii_case(name)) {
+            checks.push(Check::new(
+                CheckStatus::Fail,
+                &check_name,
+                format!("unknown provider in config, expected one of {}", known.join(", "))
+            ));
+            continue;
+        }
+        match provider["apiKey"].as_str() {
+            Some(key) if !has_api_key(key) => {
+                checks.push(Check::new(CheckStatus::Warn, &check_name, format!("api key {} is not defined", key)));
+            }
+            _ => checks.push(Check::new(CheckStatus::Pass, &check_name, "known")),
+        }
+    }
+    checks
+}
+
+pub fn check_wallets(wallets: &WalletsData) -> Vec<Check> {
+    let mut checks: Vec<Check> = wallets
+        .validate()
+        .into_iter()
+        .map(|problem| Check::new(CheckStatus::Fail, "wallets", problem))
+        .collect();
+
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    for wallet in wallets.wallets.iter() {
+        *names.entry(&wallet.name).or_default() += 1;
+    }
+    let mut shared: Vec<&str> = names
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(name, _)| name)
+        .collect();
+    shared.sort();
+    for name in shared {
+        checks.push(Check::new(
+            CheckStatus::Warn,
+            "wallets",
+            format!("more wallets are named {}, commands need --address to select one", name)
+        ));
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::new(CheckStatus::Pass, "wallets", format!("{} wallets are valid", wallets.wallets.len())));
+    }
+    checks
+}
+
+pub fn check_coins(coins: &CoinsData, wallets: Option<&WalletsData>) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    for (index, coin_def) in coins.coins.iter().enumerate() {
+        if coins.coins[..index].iter().any(|other| other.code == coin_def.code) {
+            checks.push(Check::new(CheckStatus::Fail, "coins", format!("duplicate coin {}", coin_def.code)));
+        }
+        let token_id = coin_def.ath_provider_data.as_ref().and_then(|data| data.get("token_id"));
+        if coin_def.ath_provider == AthProviderEnum::CoinGecko && token_id.is_none() {
+            checks.push(Check::new(
+                CheckStatus::Warn,
+                "coins",
+                format!("{} has no CoinGecko token_id, run coins resolve-ids", coin_def.code)
+            ));
+        }
+        if coin_def.price_provider == PriceProviderEnum::CoinGecko {
+            checks.push(Check::new(
+                CheckStatus::Fail,
+                "coins",
+                format!("{} uses CoinGecko price provider, which is not supported", coin_def.code)
+            ));
+        }
+        for alias in coin_def.aliases.iter() {
+            if alias.ratio <= 0.0 || !alias.ratio.is_finite() {
+                checks.push(Check::new(
+                    CheckStatus::Fail,
+                    "coins",
+                    format!("{} has invalid ratio {} of alias {}", coin_def.code, alias.ratio, alias.code)
+                ));
+            }
+        }
+    }
+
+    if let Some(wallets) = wallets {
+        let missing = wallets.missing_coins(coins);
+        if !missing.is_empty() {
+            checks.push(Check::new(
+                CheckStatus::Warn,
+                "coins",
+                format!("coins from wallets are not in coins config: {}, run coins sync", missing.join(", "))
+            ));
+        }
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::new(CheckStatus::Pass, "coins", format!("{} coins are valid", coins.coins.len())));
+    }
+    checks
+}
+
+// Keys of the price providers used by the coins are required, the others are only reported.
+// The CoinGecko key is needed only to list the coins for coins resolve-ids and coins sync.
+fn check_api_keys(coins: &CoinsData) -> Vec<Check> {
+    let uses = |provider: PriceProviderEnum| coins.coins.iter().any(|coin_def| coin_def.price_provider == provider);
+    let keys = [
+        (client::cryptocompare::API_KEY_ENV_PARAM, uses(PriceProviderEnum::CryptoCompare)),
+        (client::coinapi::API_KEY_ENV_PARAM, uses(PriceProviderEnum::CoinAPI)),
+    ];
+
+    let mut checks: Vec<Check> = keys
+        .into_iter()
+        .map(|(key, required)| {
+            match (has_api_key(key), required) {
+                (true, _) => Check::new(CheckStatus::Pass, key, "defined"),
+                (false, true) => Check::new(CheckStatus::Fail, key, "not defined, it is used by the coins config"),
+                (false, false) => Check::new(CheckStatus::Warn, key, "not defined"),
+            }
+        })
+        .collect();
+
+    let coingecko_key = client::coingecko::API_KEY_ENV_PARAM;
+    checks.push(match has_api_key(coingecko_key) {
+        true => Check::new(CheckStatus::Pass, coingecko_key, "defined"),
+        false => Check::new(CheckStatus::Warn, coingecko_key, "not defined, it is needed by coins resolve-ids and coins sync"),
+    });
+    checks
+}
+
+fn has_api_key(key: &str) -> bool {
+    env::var(key).is_ok_and(|value| !value.is_empty())
+}
+
+// The data dir is created by the first price update, so only its nearest existing parent has to be writable
+// when it is missing
+fn check_data_dir(dir: &Path) -> Check {
+    let name = dir.display().to_string();
+    if !dir.exists() {
+        let parent = dir.ancestors().skip(1).find(|parent| parent.is_dir()).unwrap_or(Path::new("."));
+        return match is_writable(parent) {
+            true => Check::new(CheckStatus::Pass, &name, "not present, it will be created"),
+            false => Check::new(CheckStatus::Fail, &name, "not present and cannot be created"),
+        };
+    }
+    if !dir.is_dir() {
+        return Check::new(CheckStatus::Fail, &name, "is not a directory");
+    }
+    match is_writable(dir) {
+        true => Check::new(CheckStatus::Pass, &name, "writable"),
+        false => Check::new(CheckStatus::Fail, &name, "not writable"),
+    }
+}
+
+fn is_writable(dir: &Path) -> bool {
+    let probe_file = dir.join(".crpt-doctor");
+    let writable = fs::write(&probe_file, b"").is_ok();
+    let _ = fs::remove_file(&probe_file);
+    writable
+}
+
+// Price providers without API key are skipped, the missing key is already reported.
+// The CoinGecko markets endpoint used for the ATH works without a key.
+async fn probe_providers() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let price_providers = [
+        (PriceProviderEnum::CryptoCompare, client::cryptocompare::API_KEY_ENV_PARAM),
+        (PriceProviderEnum::CoinAPI, client::coinapi::API_KEY_ENV_PARAM),
+    ];
+    for (provider_id, key) in price_providers {
+        let name = format!("{:?} prices", provider_id);
+        if !has_api_key(key) {
+            checks.push(Check::new(CheckStatus::Warn, &name, "skipped, no API key"));
+            continue;
+        }
+        let result = get_price_provider(&provider_id).get_price("BTC", &None, &[Currency::USD]).await;
+        checks.push(match result {
+            Ok(prices) if !prices.is_empty() => Check::new(CheckStatus::Pass, &name, "BTC price received"),
+            Ok(_) => Check::new(CheckStatus::Fail, &name, "no BTC price"),
+            Err(e) => Check::new(CheckStatus::Fail, &name, e.to_string()),
+        });
+    }
+
+    let token_id = HashMap::from([("token_id".to_string(), "bitcoin".to_string())]);
+    let result = get_ath_provider(&AthProviderEnum::CoinGecko)
+        .get_ath("BTC", Some(&token_id), &[Currency::USD]).await;
+    checks.push(match result {
+        Ok(aths) if !aths.is_empty() => Check::new(CheckStatus::Pass, "CoinGecko ATH", "BTC ATH received"),
+        Ok(_) => Check::new(CheckStatus::Fail, "CoinGecko ATH", "no BTC ATH"),
+        Err(e) => Check::new(CheckStatus::Fail, "CoinGecko ATH", e.to_string()),
+    });
+
+    checks
+}
+
+fn write_checklist(out: &mut impl Write, checks: &[Check]) -> io::Result<()> {
+    for check in checks {
+        let status = match check.status {
+            CheckStatus::Pass => "PASS".green(),
+            CheckStatus::Warn => "WARN".yellow(),
+            CheckStatus::Fail => "FAIL".red(),
+        };
+        writeln!(out, "[{}] {:24} {}", status, check.name, check.detail)?;
+    }
+    let count = |status: CheckStatus| checks.iter().filter(|check| check.status == status).count();
+    writeln!(
+        out,
+        "{} passed, {} warnings, {} failed",
+        count(CheckStatus::Pass),
+        count(CheckStatus::Warn),
+        count(CheckStatus::Fail)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_configs() {
+        let value = serde_json::json!({ "coins": [
+            { "code": "BTC", "priceProvider": "CryptoCompare", "athProvider": "CoinGecko" },
+            { "code": "ETH", "priceProvider": "Binance" }
+        ] });
+        let checks = check_coin_providers(&value);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].name, "coin ETH");
+
+        let value = serde_json::json!({ "providers": [
+            { "name": "CoinApi", "apiKey": "CRPT_DOCTOR_TEST_MISSING_KEY" },
+            { "name": "Binance" }
+        ] });
+        let statuses: Vec<CheckStatus> = check_app_providers(&value).into_iter().map(|check| check.status).collect();
+        assert_eq!(statuses, vec![CheckStatus::Warn, CheckStatus::Fail]);
+
+        let coins: CoinsData = serde_json::from_value(serde_json::json!({
+            "coins": [
+                { "code": "BTC", "athProviderData": { "token_id": "bitcoin" } },
+                { "code": "BTC", "athProviderData": { "token_id": "bitcoin" } },
+                { "code": "XYZ" }
+            ]
+        })).unwrap();
+        let wallets: WalletsData = serde_json::from_value(serde_json::json!({
+            "wallets": [
+                { "name": "Ledger", "kind": "Ledger", "address": "a", "holdings": [{ "coin": "DOT", "amount": -1.0 }] },
+                { "name": "Ledger", "kind": "Ledger", "address": "b", "holdings": [] }
+            ]
+        })).unwrap();
+
+        let statuses: Vec<CheckStatus> = check_coins(&coins, Some(&wallets))
+            .into_iter()
+            .map(|check| check.status)
+            .collect();
+        assert_eq!(statuses, vec![CheckStatus::Fail, CheckStatus::Warn, CheckStatus::Warn]);
+
+        // Only coins resolve-ids and coins sync need the CoinGecko key
+        let coingecko_check = check_api_keys(&coins)
+            .into_iter()
+            .find(|check| check.name == client::coingecko::API_KEY_ENV_PARAM)
+            .unwrap();
+        assert_ne!(coingecko_check.status, CheckStatus::Fail);
+
+        let statuses: Vec<CheckStatus> = check_wallets(&wallets).into_iter().map(|check| check.status).collect();
+        assert_eq!(statuses, vec![CheckStatus::Fail, CheckStatus::Warn]);
+    }
+}
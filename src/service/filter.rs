@@ -0,0 +1,96 @@
+use std::str::FromStr;
+
+use glob::{ MatchOptions, Pattern };
+use regex::{ Regex, RegexBuilder };
+
+const REGEX_PREFIX: &str = "re:";
+
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+// Case-insensitive pattern for matching names.
+// Values prefixed with "re:" are regular expressions, everything else is a glob - for example "*metamask*".
+// Plain names without wildcards match the whole name.
+#[derive(Debug, Clone)]
+pub enum NamePattern {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl FromStr for NamePattern {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        if let Some(regex) = val.strip_prefix(REGEX_PREFIX) {
+            return RegexBuilder::new(regex)
+                .case_insensitive(true)
+                .build()
+                .map(NamePattern::Regex)
+                .map_err(|e| format!("Invalid regex {}: {}", regex, e));
+        }
+        Pattern::new(val)
+            .map(NamePattern::Glob)
+            .map_err(|e| format!("Invalid pattern {}: {}", val, e))
+    }
+}
+
+impl NamePattern {
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Glob(pattern) => pattern.matches_with(name, GLOB_OPTIONS),
+            NamePattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+// Returns true when the include list is empty or the value is in it and it is not in the exclude list.
+// Values are compared case-insensitive.
+pub fn matches_values(value: &str, include: &[String], exclude: &[String]) -> bool {
+    let included = include.is_empty() || include.iter().any(|item| item.eq_ignore_ascii_case(value));
+    included && !exclude.iter().any(|item| item.eq_ignore_ascii_case(value))
+}
+
+// Same as matches_values, for name patterns
+pub fn matches_patterns(value: &str, include: &[NamePattern], exclude: &[NamePattern]) -> bool {
+    let included = include.is_empty() || include.iter().any(|pattern| pattern.matches(value));
+    included && !exclude.iter().any(|pattern| pattern.matches(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_pattern() {
+        let glob = NamePattern::from_str("*metamask*").unwrap();
+        assert!(glob.matches("Ethereum MetaMask 1"));
+        assert!(!glob.matches("Ledger"));
+
+        let exact = NamePattern::from_str("ledger nano").unwrap();
+        assert!(exact.matches("Ledger Nano"));
+        assert!(!exact.matches("Ledger Nano 2"));
+
+        let regex = NamePattern::from_str("re:^ethereum metamask [12]$").unwrap();
+        assert!(regex.matches("Ethereum MetaMask 2"));
+        assert!(!regex.matches("Ethereum MetaMask 3"));
+
+        assert!(NamePattern::from_str("re:(").is_err());
+    }
+
+    #[test]
+    fn test_matches_values_and_patterns() {
+        let include = vec!["btc".to_string(), "ETH".to_string()];
+        let exclude = vec!["eth".to_string()];
+        assert!(matches_values("BTC", &include, &[]));
+        assert!(!matches_values("ETH", &include, &exclude));
+        assert!(!matches_values("DOT", &include, &[]));
+        assert!(matches_values("DOT", &[], &exclude));
+
+        let exclude = vec![NamePattern::from_str("*exchange*").unwrap()];
+        assert!(matches_patterns("Ledger", &[], &exclude));
+        assert!(!matches_patterns("Kraken Exchange", &[], &exclude));
+    }
+}
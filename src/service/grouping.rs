@@ -0,0 +1,288 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::model::{ GroupBy, ReportOrder, ReportSortBy };
+
+use super::ReportLine;
+
+const MIXED: &str = "-";
+
+// Returns the label of the line for the grouping level
+fn group_label(line: &ReportLine, group_by: &GroupBy) -> String {
+    match group_by {
+        GroupBy::Token => line.token.clone(),
+        GroupBy::Wallet => line.wallet_name.clone(),
+        GroupBy::Kind => line.wallet_kind.clone(),
+        GroupBy::Tag => line.wallet_tag.clone().unwrap_or_else(|| MIXED.to_string()),
+        GroupBy::Chain => line.wallet_chain.clone().unwrap_or_else(|| MIXED.to_string()),
+    }
+}
+
+// Aggregates the lines into one line per combination of the group levels.
+// Every aggregated line has its group key set, which is used for nested sorting and subtotals.
+pub fn group_lines(lines: Vec<ReportLine>, group_by: &[GroupBy]) -> Vec<ReportLine> {
+    if group_by.is_empty() {
+        return lines;
+    }
+
+    let mut order: Vec<Vec<String>> = Vec::new();
+    let mut grouped: HashMap<Vec<String>, Vec<ReportLine>> = HashMap::new();
+    for line in lines {
+        let key: Vec<String> = group_by
+            .iter()
+            .map(|level| group_label(&line, level))
+            .collect();
+        if !grouped.contains_key(&key) {
+            order.push(key.clone());
+        }
+        grouped.entry(key).or_default().push(line);
+    }
+
+    order
+        .into_iter()
+        .map(|key| {
+            let group_lines = grouped.remove(&key).unwrap_or_default();
+            aggregate(key, &group_lines.iter().collect::<Vec<_>>())
+        })
+        .collect()
+}
+
+// Aggregates the lines of one group.
+// Value is summed and 24h change is weighted by value. Amount and market cap are kept only
+// when the group contains a single token, as they cannot be added across tokens.
+// Text fields are kept when they are the same for all lines, otherwise they are "-".
+pub fn aggregate(group: Vec<String>, lines: &[&ReportLine]) -> ReportLine {
+    let value: f64 = lines.iter().map(|line| line.value).sum();
+    let single_token = lines.windows(2).all(|pair| pair[0].token == pair[1].token);
+
+    let change_24h = if value != 0.0 {
+        lines.iter().map(|line| line.value * line.change_24h).sum::<f64>() / value
+    } else {
+        0.0
+    };
+
+    ReportLine {
+        token: common(lines, |line| &line.token),
+        amount: if single_token { lines.iter().map(|line| line.amount).sum() } else { 0.0 },
+        value,
+        allocation: 0.0,
+        mkt_cap: if single_token { lines.first().map_or(0.0, |line| line.mkt_cap) } else { 0.0 },
+        change_24h,
+        wallet_name: common(lines, |line| &line.wallet_name),
+        wallet_kind: common(lines, |line| &line.wallet_kind),
+        wallet_address: common(lines, |line| &line.wallet_address),
+        wallet_tag: common_option(lines, |line| &line.wallet_tag),
+        wallet_chain: common_option(lines, |line| &line.wallet_chain),
+        group,
+        ath: if single_token { lines.first().and_then(|line| line.ath) } else { None },
+        from_ath: if single_token { lines.first().and_then(|line| line.from_ath) } else { None },
+        atl: if single_token { lines.first().and_then(|line| line.atl) } else { None },
+        ath_date: if single_token { lines.first().and_then(|line| line.ath_date.clone()) } else { None },
+        value_at_ath: value_at_ath(lines),
+    }
+}
+
+// Sums the values at ATH, lines without ATH data are counted with their current value.
+// Returns None when no line has ATH data.
+pub fn value_at_ath(lines: &[&ReportLine]) -> Option<f64> {
+    if lines.iter().all(|line| line.value_at_ath.is_none()) {
+        return None;
+    }
+    Some(lines.iter().map(|line| line.value_at_ath.unwrap_or(line.value)).sum())
+}
+
+fn common<F>(lines: &[&ReportLine], field: F) -> String where F: Fn(&ReportLine) -> &String {
+    match lines.first() {
+        Some(first) if lines.iter().all(|line| field(line) == field(first)) => field(first).clone(),
+        _ => MIXED.to_string(),
+    }
+}
+
+fn common_option<F>(lines: &[&ReportLine], field: F) -> Option<String>
+    where F: Fn(&ReportLine) -> &Option<String>
+{
+    match lines.first() {
+        Some(first) if lines.iter().all(|line| field(line) == field(first)) => field(first).clone(),
+        _ => None,
+    }
+}
+
+// Sorts the lines. With nested grouping, the outer groups are ordered by their aggregated values
+// and the lines are sorted inside of their groups, so the lines of one group stay together.
+pub fn sort_lines(lines: Vec<ReportLine>, levels: usize, sort_by: &ReportSortBy, order: &ReportOrder) -> Vec<ReportLine> {
+    sort_level(lines, 0, levels, sort_by, order)
+}
+
+fn sort_level(
+    mut lines: Vec<ReportLine>,
+    depth: usize,
+    levels: usize,
+    sort_by: &ReportSortBy,
+    order: &ReportOrder
+) -> Vec<ReportLine> {
+    if depth + 1 >= levels {
+        lines.sort_by(|a, b| compare(a, b, sort_by, order));
+        return lines;
+    }
+
+    let mut keys: Vec<String> = Vec::new();
+    let mut partitions: HashMap<String, Vec<ReportLine>> = HashMap::new();
+    for line in lines {
+        let key = line.group[depth].clone();
+        if !partitions.contains_key(&key) {
+            keys.push(key.clone());
+        }
+        partitions.entry(key).or_default().push(line);
+    }
+
+    let mut aggregates: Vec<(String, ReportLine)> = keys
+        .into_iter()
+        .map(|key| {
+            let partition = &partitions[&key];
+            let mut aggregated = aggregate(Vec::new(), &partition.iter().collect::<Vec<_>>());
+            // Groups are ordered by their name when sorting by token
+            aggregated.token = key.clone();
+            (key, aggregated)
+        })
+        .collect();
+    aggregates.sort_by(|a, b| compare(&a.1, &b.1, sort_by, order));
+
+    aggregates
+        .into_iter()
+        .flat_map(|(key, _)| {
+            let partition = partitions.remove(&key).unwrap_or_default();
+            sort_level(partition, depth + 1, levels, sort_by, order)
+        })
+        .collect()
+}
+
+fn compare(a: &ReportLine, b: &ReportLine, sort_by: &ReportSortBy, order: &ReportOrder) -> Ordering {
+    let ordering = match sort_by {
+        ReportSortBy::Value => a.value.total_cmp(&b.value),
+        ReportSortBy::Amount => a.amount.total_cmp(&b.amount),
+        ReportSortBy::MktCap => a.mkt_cap.total_cmp(&b.mkt_cap),
+        ReportSortBy::Change24h => a.change_24h.total_cmp(&b.change_24h),
+        ReportSortBy::Token => a.token.cmp(&b.token),
+        ReportSortBy::FromAth => a.from_ath.unwrap_or(0.0).total_cmp(&b.from_ath.unwrap_or(0.0)),
+    };
+    match order {
+        ReportOrder::Asc => ordering,
+        ReportOrder::Desc => ordering.reverse(),
+    }
+}
+
+// Returns subtotals for every outer group level of the lines, in the order in which the groups appear.
+// For lines grouped by kind and token, there is one subtotal per kind.
+pub fn subtotals(lines: &[ReportLine]) -> Vec<ReportLine> {
+    let mut order: Vec<Vec<String>> = Vec::new();
+    let mut grouped: HashMap<Vec<String>, Vec<&ReportLine>> = HashMap::new();
+
+    for line in lines {
+        for depth in 1..line.group.len() {
+            let prefix = line.group[..depth].to_vec();
+            if !grouped.contains_key(&prefix) {
+                order.push(prefix.clone());
+            }
+            grouped.entry(prefix).or_default().push(line);
+        }
+    }
+
+    order
+        .into_iter()
+        .map(|prefix| {
+            let group_lines = grouped.remove(&prefix).unwrap_or_default();
+            aggregate(prefix, &group_lines)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(token: &str, kind: &str, value: f64, change_24h: f64) -> ReportLine {
+        ReportLine {
+            token: token.to_string(),
+            amount: 1.0,
+            value,
+            mkt_cap: 1000.0,
+            change_24h,
+            wallet_name: format!("{} wallet", kind),
+            wallet_kind: kind.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_group_lines_aggregation() {
+        let lines = || vec![
+            line("BTC", "Ledger", 300.0, 10.0),
+            line("ETH", "Ledger", 100.0, -10.0),
+            line("BTC", "Exchange", 100.0, 10.0)
+        ];
+
+        let by_token = group_lines(lines(), &[GroupBy::Token]);
+        let btc = by_token.iter().find(|line| line.token == "BTC").unwrap();
+        assert_eq!(btc.group, vec!["BTC"]);
+        assert_eq!(btc.amount, 2.0);
+        assert_eq!(btc.value, 400.0);
+        assert_eq!(btc.mkt_cap, 1000.0);
+        assert_eq!(btc.wallet_kind, "-");
+
+        let by_kind = group_lines(lines(), &[GroupBy::Kind]);
+        let ledger = by_kind.iter().find(|line| line.group == vec!["Ledger"]).unwrap();
+        assert_eq!(ledger.token, "-");
+        assert_eq!(ledger.amount, 0.0);
+        assert_eq!(ledger.mkt_cap, 0.0);
+        assert_eq!(ledger.wallet_name, "Ledger wallet");
+        assert_eq!(ledger.change_24h, 5.0);
+    }
+
+    #[test]
+    fn test_aggregate_value_at_ath() {
+        let mut btc = line("BTC", "Ledger", 300.0, 0.0);
+        btc.ath = Some(100.0);
+        btc.from_ath = Some(-50.0);
+        btc.value_at_ath = Some(600.0);
+        let eth = line("ETH", "Ledger", 100.0, 0.0);
+
+        let mixed = aggregate(Vec::new(), &[&btc, &eth]);
+        assert_eq!(mixed.value_at_ath, Some(700.0));
+        assert_eq!(mixed.ath, None);
+
+        let single = aggregate(Vec::new(), &[&btc]);
+        assert_eq!(single.from_ath, Some(-50.0));
+        assert_eq!(aggregate(Vec::new(), &[&eth]).value_at_ath, None);
+    }
+
+    #[test]
+    fn test_nested_sort_and_subtotals() {
+        let lines = vec![
+            line("BTC", "Exchange", 50.0, 0.0),
+            line("BTC", "Ledger", 300.0, 0.0),
+            line("ETH", "Exchange", 60.0, 0.0),
+            line("ETH", "Ledger", 100.0, 0.0),
+            line("DOT", "Ledger", 10.0, 0.0)
+        ];
+        let grouped = group_lines(lines, &[GroupBy::Kind, GroupBy::Token]);
+        let sorted = sort_lines(grouped, 2, &ReportSortBy::Value, &ReportOrder::Desc);
+
+        let order: Vec<(String, String)> = sorted
+            .iter()
+            .map(|line| (line.group[0].clone(), line.group[1].clone()))
+            .collect();
+        assert_eq!(order, vec![
+            ("Ledger".to_string(), "BTC".to_string()),
+            ("Ledger".to_string(), "ETH".to_string()),
+            ("Ledger".to_string(), "DOT".to_string()),
+            ("Exchange".to_string(), "ETH".to_string()),
+            ("Exchange".to_string(), "BTC".to_string())
+        ]);
+
+        let subtotals = subtotals(&sorted);
+        assert_eq!(subtotals.len(), 2);
+        assert_eq!(subtotals[0].group, vec!["Ledger"]);
+        assert_eq!(subtotals[0].value, 410.0);
+        assert_eq!(subtotals[1].value, 110.0);
+    }
+}
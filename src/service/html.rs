@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use chrono::{ Local, NaiveDateTime };
+
+use crate::Result;
+
+use super::report::readable_mkt_cap;
+use super::{ HoldingsReport, ReportLine };
+
+// Slices after this count are merged into "Other" in the pie charts
+const MAX_PIE_SLICES: usize = 9;
+
+const PALETTE: [&str; 10] = [
+    "#4e79a7",
+    "#f28e2b",
+    "#e15759",
+    "#76b7b2",
+    "#59a14f",
+    "#edc948",
+    "#b07aa1",
+    "#ff9da7",
+    "#9c755f",
+    "#bab0ac",
+];
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em; color: #222; }
+h1 { margin-bottom: 0.2em; }
+.meta { color: #666; margin-top: 0; }
+.charts { display: flex; flex-wrap: wrap; gap: 2em; }
+.chart h2 { font-size: 1.1em; }
+table { border-collapse: collapse; margin-top: 1em; }
+th, td { padding: 4px 10px; border-bottom: 1px solid #ddd; }
+th { text-align: left; background: #f4f4f4; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+.up { color: #2a8a2a; }
+.down { color: #c0392b; }
+tfoot td { font-weight: bold; }
+"#;
+
+// Writes the holdings report with charts to a single html file, which does not need any external assets.
+// The allocation charts are drawn from the ungrouped holding lines.
+pub fn write_html_report(
+    path: &Path,
+    report: &HoldingsReport,
+    holding_lines: &[ReportLine],
+    history: &[(NaiveDateTime, f64)]
+) -> Result<()> {
+    let generated = Local::now().format("%Y-%m-%d %H:%M").to_string();
+    let html = render_html_report(report, holding_lines, history, &generated);
+    fs::write(path, html).map_err(|e| format!("Cannot write html report {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+fn render_html_report(
+    report: &HoldingsReport,
+    holding_lines: &[ReportLine],
+    history: &[(NaiveDateTime, f64)],
+    generated: &str
+) -> String {
+    let currency = report.currency.to_uppercase_str();
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Portfolio report</title>\n");
+    let _ = writeln!(html, "<style>{}</style>\n</head>\n<body>", STYLE);
+    html.push_str("<h1>Portfolio report</h1>\n");
+    let _ = writeln!(
+        html,
+        "<p class=\"meta\">Generated {} &middot; Total value <strong>{:.2} {}</strong> &middot; {} holdings</p>",
+        escape_html(generated),
+        report.totals.value,
+        currency,
+        report.totals.lines
+    );
+
+    html.push_str("<div class=\"charts\">\n");
+    let by_token = allocation_by(holding_lines, |line| line.token.clone());
+    let by_kind = allocation_by(holding_lines, |line| line.wallet_kind.clone());
+    push_chart(&mut html, "Allocation by token", &pie_chart_svg(&by_token));
+    push_chart(&mut html, "Allocation by wallet kind", &pie_chart_svg(&by_kind));
+    if history.len() > 1 {
+        let title = format!("Value over time ({})", currency);
+        push_chart(&mut html, &title, &line_chart_svg(history));
+    }
+    html.push_str("</div>\n");
+
+    html.push_str(&holdings_table(report));
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn push_chart(html: &mut String, title: &str, svg: &str) {
+    let _ = writeln!(html, "<div class=\"chart\">\n<h2>{}</h2>\n{}</div>", escape_html(title), svg);
+}
+
+fn holdings_table(report: &HoldingsReport) -> String {
+    let mut html = String::new();
+    html.push_str("<table>\n<thead><tr>");
+    for header in ["Token", "Amount", "Value", "Alloc %", "Mkt.Cap", "24H %", "Wallet", "Kind", "Address"] {
+        let _ = write!(html, "<th>{}</th>", header);
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+
+    for line in report.lines.iter() {
+        let change_class = if line.change_24h > 0.0 { "up" } else { "down" };
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{:.6}</td><td class=\"num\">{:.2}</td><td class=\"num\">{:.2}%</td><td class=\"num\">{}</td><td class=\"num {}\">{:.2}%</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&line.token),
+            line.amount,
+            line.value,
+            line.allocation,
+            readable_mkt_cap(line.mkt_cap),
+            change_class,
+            line.change_24h,
+            escape_html(&line.wallet_name),
+            escape_html(&line.wallet_kind),
+            escape_html(&line.wallet_address)
+        );
+    }
+
+    let _ = writeln!(
+        html,
+        "</tbody>\n<tfoot><tr><td>Sum</td><td></td><td class=\"num\">{:.2}</td><td colspan=\"6\"></td></tr></tfoot>\n</table>",
+        report.totals.value
+    );
+    html
+}
+
+// Sums the values by the key, ordered from the largest. The smallest ones are merged into "Other".
+fn allocation_by<F>(lines: &[ReportLine], key: F) -> Vec<(String, f64)> where F: Fn(&ReportLine) -> String {
+    let mut grouped: HashMap<String, f64> = HashMap::new();
+    for line in lines {
+        *grouped.entry(key(line)).or_default() += line.value;
+    }
+
+    let mut slices: Vec<(String, f64)> = grouped
+        .into_iter()
+        .filter(|(_, value)| *value > 0.0)
+        .collect();
+    slices.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    if slices.len() > MAX_PIE_SLICES {
+        let other: f64 = slices.drain(MAX_PIE_SLICES - 1..).map(|(_, value)| value).sum();
+        slices.push(("Other".to_string(), other));
+    }
+    slices
+}
+
+fn pie_chart_svg(slices: &[(String, f64)]) -> String {
+    let total: f64 = slices.iter().map(|(_, value)| value).sum();
+    if total <= 0.0 {
+        return "<p>No data</p>\n".to_string();
+    }
+
+    let (cx, cy, r) = (110.0, 110.0, 100.0);
+    let height = f64::max(220.0, 20.0 * slices.len() as f64 + 20.0);
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"440\" height=\"{}\" viewBox=\"0 0 440 {}\">",
+        height,
+        height
+    );
+
+    let mut start = -PI / 2.0;
+    for (index, (label, value)) in slices.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        let share = value / total;
+        let title = format!("{}: {:.2} ({:.1}%)", escape_html(label), value, share * 100.0);
+
+        if share >= 0.9999 {
+            let _ = writeln!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"><title>{}</title></circle>", cx, cy, r, color, title);
+        } else {
+            let end = start + share * 2.0 * PI;
+            let large_arc = if share > 0.5 { 1 } else { 0 };
+            let _ = writeln!(
+                svg,
+                "<path d=\"M {:.2} {:.2} L {:.2} {:.2} A {} {} 0 {} 1 {:.2} {:.2} Z\" fill=\"{}\" stroke=\"#fff\"><title>{}</title></path>",
+                cx,
+                cy,
+                cx + r * start.cos(),
+                cy + r * start.sin(),
+                r,
+                r,
+                large_arc,
+                cx + r * end.cos(),
+                cy + r * end.sin(),
+                color,
+                title
+            );
+            start = end;
+        }
+
+        let legend_y = 20.0 + 20.0 * index as f64;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"235\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/><text x=\"253\" y=\"{}\" font-size=\"12\">{} {:.1}%</text>",
+            legend_y - 10.0,
+            color,
+            legend_y,
+            escape_html(label),
+            share * 100.0
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn line_chart_svg(points: &[(NaiveDateTime, f64)]) -> String {
+    let (width, height) = (640.0, 240.0);
+    let (left, right, top, bottom) = (70.0, 20.0, 15.0, 30.0);
+
+    let first_time = points.first().map(|(time, _)| time.and_utc().timestamp()).unwrap_or(0) as f64;
+    let last_time = points.last().map(|(time, _)| time.and_utc().timestamp()).unwrap_or(0) as f64;
+    let time_span = f64::max(last_time - first_time, 1.0);
+
+    let min_value = points.iter().map(|(_, value)| *value).fold(f64::INFINITY, f64::min);
+    let max_value = points.iter().map(|(_, value)| *value).fold(f64::NEG_INFINITY, f64::max);
+    let value_span = if max_value - min_value > 0.0 { max_value - min_value } else { 1.0 };
+
+    let x = |time: &NaiveDateTime| left + ((time.and_utc().timestamp() as f64 - first_time) / time_span) * (width - left - right);
+    let y = |value: f64| top + (1.0 - (value - min_value) / value_span) * (height - top - bottom);
+
+    let polyline: Vec<String> = points
+        .iter()
+        .map(|(time, value)| format!("{:.1},{:.1}", x(time), y(*value)))
+        .collect();
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        width,
+        height,
+        width,
+        height
+    );
+    let _ = writeln!(
+        svg,
+        "<line x1=\"{left}\" y1=\"{top}\" x2=\"{left}\" y2=\"{b}\" stroke=\"#999\"/><line x1=\"{left}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/>",
+        left = left,
+        top = top,
+        b = height - bottom,
+        r = width - right
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{:.2}</text><text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{:.2}</text>",
+        left - 5.0,
+        top + 4.0,
+        max_value,
+        left - 5.0,
+        height - bottom,
+        min_value
+    );
+    if let (Some((first, _)), Some((last, _))) = (points.first(), points.last()) {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-size=\"11\">{}</text><text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{}</text>",
+            left,
+            height - 10.0,
+            first.format("%Y-%m-%d"),
+            width - right,
+            height - 10.0,
+            last.format("%Y-%m-%d")
+        );
+    }
+    let _ = writeln!(
+        svg,
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+        polyline.join(" "),
+        PALETTE[0]
+    );
+    for (time, value) in points {
+        let _ = writeln!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"><title>{}: {:.2}</title></circle>",
+            x(time),
+            y(*value),
+            PALETTE[0],
+            time.format("%Y-%m-%d %H:%M"),
+            value
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Currency;
+
+    fn line(token: &str, kind: &str, value: f64) -> ReportLine {
+        ReportLine {
+            token: token.to_string(),
+            amount: 1.0,
+            value,
+            allocation: 0.0,
+            mkt_cap: 0.0,
+            change_24h: 0.0,
+            wallet_name: "<script>".to_string(),
+            wallet_kind: kind.to_string(),
+            wallet_address: "-".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_allocation_by_merges_small_slices() {
+        let lines: Vec<ReportLine> = (0..12)
+            .map(|i| line(&format!("T{:02}", i), "Ledger", 100.0 - i as f64))
+            .collect();
+        let slices = allocation_by(&lines, |line| line.token.clone());
+        assert_eq!(slices.len(), MAX_PIE_SLICES);
+        assert_eq!(slices[0].0, "T00");
+        assert_eq!(slices.last().unwrap().0, "Other");
+        let total: f64 = slices.iter().map(|(_, value)| value).sum();
+        assert_eq!(total, lines.iter().map(|line| line.value).sum::<f64>());
+    }
+
+    #[test]
+    fn test_render_html_report_is_self_contained() {
+        let lines = vec![line("BTC", "Ledger", 100.0), line("ETH", "MetaMask", 50.0)];
+        let report = HoldingsReport::new(lines.clone(), Currency::USD);
+        let history = vec![
+            (NaiveDateTime::parse_from_str("202412081713", "%Y%m%d%H%M").unwrap(), 120.0),
+            (NaiveDateTime::parse_from_str("202412091713", "%Y%m%d%H%M").unwrap(), 150.0)
+        ];
+        let html = render_html_report(&report, &lines, &history, "2024-12-09 17:13");
+
+        assert!(html.contains("Allocation by token"));
+        assert!(html.contains("Allocation by wallet kind"));
+        assert!(html.contains("Value over time (USD)"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("src=") && !html.contains("href="));
+    }
+}
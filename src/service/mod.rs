@@ -1,25 +1,43 @@
-use std::collections::HashMap;
-use colored::*;
-use report::{colored_change_percent, readable_mkt_cap};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use tracing::warn;
 
 use crate::{
-    config::wallets::WalletsData,
-    model::{ Currency, PriceInfo, ReportOrder, ReportSortBy },
+    coins::PricesSnapshot,
+    config::wallets::{ CoinHolding, WalletDef, WalletsData },
+    model::{ AthInfo, Currency, GroupBy, PriceInfo, ReportOrder, ReportSortBy },
 };
-use ordered_float::OrderedFloat;
-
-
 
 mod report;
+mod output;
+mod html;
+mod summary;
+mod grouping;
+mod filter;
+pub mod doctor;
+
+pub use output::{ write_ath_history, write_report, write_wallets_report };
+pub use html::write_html_report;
+pub use summary::{ allocation_summary, AllocationSummary };
+pub use filter::NamePattern;
+pub use report::readable_mkt_cap;
 
+// Filters of the holdings report. Empty lists do not filter anything.
 #[derive(Debug)]
 pub struct ReportFilter {
-    pub coin: Option<String>,
-    pub wallet_name: Option<String>,
-    pub wallet_kind: Option<String>,
-    pub wallet_address: Option<String>,
+    pub tokens: Vec<String>,
+    pub exclude_tokens: Vec<String>,
+    pub wallet_names: Vec<NamePattern>,
+    pub exclude_wallet_names: Vec<NamePattern>,
+    pub wallet_kinds: Vec<String>,
+    pub exclude_wallet_kinds: Vec<String>,
+    pub wallet_addresses: Vec<String>,
+    // Thresholds for the value of the report lines, applied after grouping
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
     pub currency: Currency,
-    pub group_by_token: bool,
+    // Grouping levels, the first one is the outermost. Empty means no grouping.
+    pub group_by: Vec<GroupBy>,
     pub sort_by: ReportSortBy,
     pub order: ReportOrder,
 }
@@ -27,12 +45,17 @@ pub struct ReportFilter {
 impl Default for ReportFilter {
     fn default() -> Self {
         Self {
-            coin: None,
-            wallet_name: None,
-            wallet_kind: None,
-            wallet_address: None,
+            tokens: Vec::new(),
+            exclude_tokens: Vec::new(),
+            wallet_names: Vec::new(),
+            exclude_wallet_names: Vec::new(),
+            wallet_kinds: Vec::new(),
+            exclude_wallet_kinds: Vec::new(),
+            wallet_addresses: Vec::new(),
+            min_value: None,
+            max_value: None,
             currency: Currency::USD,
-            group_by_token: false,
+            group_by: Vec::new(),
             sort_by: ReportSortBy::Value,
             order: ReportOrder::Desc,
         }
@@ -40,194 +63,281 @@ impl Default for ReportFilter {
 }
 
 impl ReportFilter {
-    pub fn new(
-        coin: Option<String>,
-        wallet_name: Option<String>,
-        wallet_kind: Option<String>,
-        wallet_address: Option<String>,
-        currency: Currency,
-        group_by_token: bool,
-        sort_by: ReportSortBy,
-        order: ReportOrder
-    ) -> Self {
-        Self {
-            coin,
-            wallet_name,
-            wallet_kind,
-            wallet_address,
-            currency,
-            group_by_token,
-            sort_by,
-            order,
-        }
+    pub fn matches_token(&self, token: &str) -> bool {
+        filter::matches_values(token, &self.tokens, &self.exclude_tokens)
+    }
+
+    fn matches_value(&self, value: f64) -> bool {
+        self.min_value.is_none_or(|min_value| value >= min_value) &&
+            self.max_value.is_none_or(|max_value| value <= max_value)
     }
 }
 
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct ReportLine {
-    token: String,
-    amount: f64,
-    value: f64,
-    mkt_cap: f64,
-    change_24h: f64,
-    wallet_name: String,
-    #[allow(unused)]
-    wallet_kind: String,
-    wallet_address: String,
+    pub token: String,
+    pub amount: f64,
+    pub value: f64,
+    // Share of the line in the value of the report, in percent
+    pub allocation: f64,
+    pub mkt_cap: f64,
+    pub change_24h: f64,
+    pub wallet_name: String,
+    pub wallet_kind: String,
+    pub wallet_address: String,
+    pub wallet_tag: Option<String>,
+    pub wallet_chain: Option<String>,
+    // Values of the grouping levels of the line, empty when the report is not grouped
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub group: Vec<String>,
+    // All time data, filled when ATH data are passed to the report
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ath: Option<f64>,
+    // Distance of the current price from ATH, in percent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_ath: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub atl: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ath_date: Option<String>,
+    // Value of the holding if the token was at its ATH price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_at_ath: Option<f64>,
 }
 
+#[derive(Debug, Serialize)]
 pub struct ListWalletLine {
     wallet_name: String,
-    #[allow(unused)]
     wallet_kind: String,
     wallet_address: String,
+    holdings: usize,
+}
+
+// Holdings report as it is written in all output formats
+#[derive(Debug, Serialize)]
+pub struct HoldingsReport {
+    pub currency: Currency,
+    pub lines: Vec<ReportLine>,
+    // Subtotals of the outer groups when the report has nested grouping
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subtotals: Vec<ReportLine>,
+    pub totals: HoldingsTotals,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<AllocationSummary>,
+    // Value changes since the previous refresh of holdings --watch, shown only in the table
+    #[serde(skip)]
+    pub changes: Option<ValueChanges>,
+}
+
+#[derive(Debug)]
+pub struct ValueChanges {
+    // Change of every line, None for the lines not in the previous report
+    pub lines: Vec<Option<f64>>,
+    pub total: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HoldingsTotals {
+    pub lines: usize,
+    pub amount: f64,
+    pub value: f64,
+    // Projected value of the portfolio with all tokens at their ATH.
+    // Tokens without ATH data are counted with their current value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_at_ath: Option<f64>,
+}
+
+impl HoldingsReport {
+    pub fn new(lines: Vec<ReportLine>, currency: Currency) -> Self {
+        let totals = HoldingsTotals {
+            lines: lines.len(),
+            amount: lines.iter().map(|line| line.amount).sum(),
+            value: lines.iter().map(|line| line.value).sum(),
+            value_at_ath: grouping::value_at_ath(&lines.iter().collect::<Vec<_>>()),
+        };
+        let mut subtotals = grouping::subtotals(&lines);
+        for subtotal in subtotals.iter_mut() {
+            subtotal.allocation = summary::percent(subtotal.value, totals.value);
+        }
+        Self { currency, lines, subtotals, totals, summary: None, changes: None }
+    }
+
+    // True when the lines were joined with all time data
+    pub fn has_ath_data(&self) -> bool {
+        self.totals.value_at_ath.is_some()
+    }
+
+    pub fn with_summary(mut self, summary: AllocationSummary) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+
+    // Lines are matched by token, wallet and group, so the changes do not depend on the sorting
+    pub fn with_changes(mut self, previous: &HoldingsReport) -> Self {
+        let same_line = |line: &ReportLine, other: &ReportLine| {
+            line.token == other.token &&
+                line.wallet_name == other.wallet_name &&
+                line.wallet_address == other.wallet_address &&
+                line.group == other.group
+        };
+        let lines = self.lines
+            .iter()
+            .map(|line| {
+                previous.lines
+                    .iter()
+                    .find(|other| same_line(line, other))
+                    .map(|other| line.value - other.value)
+            })
+            .collect();
+        self.changes = Some(ValueChanges { lines, total: self.totals.value - previous.totals.value });
+        self
+    }
+}
+
+// Wallets report as it is written in all output formats
+#[derive(Debug, Serialize)]
+pub struct WalletsReport {
+    pub wallets: Vec<ListWalletLine>,
+    pub totals: WalletsTotals,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletsTotals {
+    pub wallets: usize,
+    pub holdings: usize,
+}
+
+impl WalletsReport {
+    pub fn new(wallets: Vec<ListWalletLine>) -> Self {
+        let totals = WalletsTotals {
+            wallets: wallets.len(),
+            holdings: wallets.iter().map(|line| line.holdings).sum(),
+        };
+        Self { wallets, totals }
+    }
 }
 
+// Returns the report lines for the filtered holdings.
+// When ATH data are not empty, the lines have also the all time data columns.
 pub fn report_holdings(
     wallets_data: &WalletsData,
-    prices: &Vec<PriceInfo>,
+    prices: &[PriceInfo],
+    ath_data: &[AthInfo],
+    filter: &ReportFilter
+) -> Vec<ReportLine> {
+    group_report_lines(holding_lines(wallets_data, prices, ath_data, filter), filter)
+}
+
+// Returns one line per holding of the filtered wallets, before grouping and the value filter.
+// Summaries and allocation charts are computed from these lines, so they do not depend on grouping.
+pub fn holding_lines(
+    wallets_data: &WalletsData,
+    prices: &[PriceInfo],
+    ath_data: &[AthInfo],
     filter: &ReportFilter
 ) -> Vec<ReportLine> {
     let mut report_lines = Vec::new();
 
     for wallet in wallets_data.wallets.iter() {
-        if let Some(wallet_address) = filter.wallet_address.as_ref() {
-            if wallet.address != *wallet_address {
-                continue;
-            }
-        }
-        if let Some(wallet_kind) = filter.wallet_kind.as_ref() {
-            if wallet.kind != *wallet_kind {
-                continue;
-            }
-        }
-        if let Some(wallet_name) = filter.wallet_name.as_ref() {
-            if wallet.name != *wallet_name {
-                continue;
-            }
+        if !wallet_matches(wallet, filter) {
+            continue;
         }
         for holding in wallet.holdings.iter() {
-            if let Some(coin) = filter.coin.as_ref() {
-                if holding.coin != *coin {
-                    continue;
-                }
+            if !holding_matches(holding, filter) {
+                continue;
             }
-            let Some(price_info) = find_price_info(&holding.coin, &prices, &filter.currency) else {
-                println!("Cannot find price for {}", holding.coin);
+            let Some(price_info) = find_price_info(&holding.coin, prices, &filter.currency) else {
+                warn!(coin = %holding.coin, "Cannot find price");
                 continue;
             };
             let val_of_coin = holding.amount * price_info.value;
+            let ath_info = find_ath_info(&holding.coin, ath_data, &filter.currency);
 
             report_lines.push(ReportLine {
                 token: holding.coin.clone(),
                 amount: holding.amount,
                 value: val_of_coin,
+                allocation: 0.0,
                 mkt_cap: price_info.market_cap,
                 change_24h: price_info.change_24h,
                 wallet_name: wallet.name.clone(),
                 wallet_kind: wallet.kind.clone(),
                 wallet_address: wallet.address.clone(),
+                wallet_tag: wallet.tag.clone(),
+                wallet_chain: wallet.chain.clone(),
+                group: Vec::new(),
+                ath: ath_info.map(|info| info.ath),
+                from_ath: ath_info
+                    .filter(|info| info.ath > 0.0)
+                    .map(|info| (price_info.value / info.ath - 1.0) * 100.0),
+                atl: ath_info.map(|info| info.atl),
+                ath_date: ath_info.map(|info| info.ath_date.clone()),
+                value_at_ath: ath_info.map(|info| holding.amount * info.ath),
             });
         }
     }
-
-    if filter.group_by_token {
-        report_lines = group_by_token(&report_lines);
-    }
-
-    match filter.sort_by {
-        ReportSortBy::Value => { report_lines.sort_by_key(|line| OrderedFloat(line.value)) },
-        ReportSortBy::Amount => { report_lines.sort_by_key(|line| OrderedFloat(line.amount)) },
-        ReportSortBy::MktCap => { report_lines.sort_by_key(|line| OrderedFloat(line.mkt_cap)) },
-        ReportSortBy::Change24h => { report_lines.sort_by_key(|line| OrderedFloat(line.change_24h)) },
-        ReportSortBy::Token => { report_lines.sort_by(|a,b| a.token.cmp(&b.token)) },
-    }
-
-    if filter.order == ReportOrder::Desc {
-        report_lines.reverse();
-    }
-
     report_lines
 }
 
-fn group_by_token(report_lines: &Vec<ReportLine>) -> Vec<ReportLine> {
-    let mut grouped: HashMap<String, (f64, f64, f64, f64)> = HashMap::new();
+// Groups, filters by value and sorts the holding lines, and computes their allocation
+pub fn group_report_lines(report_lines: Vec<ReportLine>, filter: &ReportFilter) -> Vec<ReportLine> {
+    let mut report_lines = grouping::group_lines(report_lines, &filter.group_by);
+    report_lines.retain(|line| filter.matches_value(line.value));
 
-    for line in report_lines {
-        let entry = grouped.entry(line.token.clone()).or_insert((0.0, 0.0, 0.0, 0.0));
-        entry.0 += line.amount;
-        entry.1 += line.value;
-        entry.2 = line.mkt_cap;
-        entry.3 = line.change_24h;
+    let total_value: f64 = report_lines.iter().map(|line| line.value).sum();
+    for line in report_lines.iter_mut() {
+        line.allocation = summary::percent(line.value, total_value);
     }
 
-    grouped
-        .into_iter()
-        .map(|(token, (total_amount, total_value, mkt_cap, change_24h))| ReportLine {
-            token,
-            amount: total_amount,
-            value: total_value,
-            mkt_cap,
-            change_24h,
-            wallet_name: "-".to_string(),
-            wallet_kind: "-".to_string(),
-            wallet_address: "-".to_string(),
+    grouping::sort_lines(report_lines, filter.group_by.len(), &filter.sort_by, &filter.order)
+}
+
+// Returns the value of the filtered holdings for every prices snapshot.
+// Holdings without price in the snapshot are not counted.
+pub fn portfolio_value_history(
+    wallets_data: &WalletsData,
+    history: &[PricesSnapshot],
+    filter: &ReportFilter
+) -> Vec<(NaiveDateTime, f64)> {
+    history
+        .iter()
+        .map(|snapshot| {
+            let value = wallets_data.wallets
+                .iter()
+                .filter(|wallet| wallet_matches(wallet, filter))
+                .flat_map(|wallet| wallet.holdings.iter())
+                .filter(|holding| holding_matches(holding, filter))
+                .filter_map(|holding| {
+                    find_price_info(&holding.coin, &snapshot.prices, &filter.currency)
+                        .map(|price_info| holding.amount * price_info.value)
+                })
+                .sum();
+            (snapshot.timestamp, value)
         })
         .collect()
 }
 
-pub fn write_report(report_lines: &Vec<ReportLine>) {
-    println!(
-        "-------------------------------------------------------------------------------------------------------"
-    );
-    println!(
-        "{:8}| {:14} | {:12} | {:10} | {:10} | {:20} | {:32}",
-        "Token",
-        "Amount",
-        "Value",
-        "Mkt.Cap",
-        "24H %",
-        "Wallet",
-        "Address"
-    );
-    println!(
-        "-------------------------------------------------------------------------------------------------------"
-    );
-
-    let mut sum = 0.0;
-    let mut amount = 0.0;
-    for line in report_lines {
-        sum += line.value;
-        amount += line.amount;
-
-        println!(
-            "{:8}| {:14.6} | {:12.2} | {:10} | {:10} | {:20} | {:32}",
-            line.token,
-            line.amount,
-            line.value,
-            readable_mkt_cap(line.mkt_cap),
-            colored_change_percent(line.change_24h),
-            line.wallet_name,
-            line.wallet_address
-        );
-    }
+fn wallet_matches(wallet: &WalletDef, filter: &ReportFilter) -> bool {
+    filter::matches_values(&wallet.address, &filter.wallet_addresses, &[]) &&
+        filter::matches_values(&wallet.kind, &filter.wallet_kinds, &filter.exclude_wallet_kinds) &&
+        filter::matches_patterns(&wallet.name, &filter.wallet_names, &filter.exclude_wallet_names)
+}
 
-    println!(
-        "-----------------------------------------------------------------------------------------------------"
-    );
-    println!("Amount  | {:14.6} |", amount);
-    println!("Sum     | {:14.2} |", sum);
-    println!(
-        "-------------------------------------------------------------------------------------------------------"
-    );
+fn holding_matches(holding: &CoinHolding, filter: &ReportFilter) -> bool {
+    filter.matches_token(&holding.coin)
 }
 
-fn find_price_info<'a>(coin: &str, prices: &'a Vec<PriceInfo>, currency: &Currency) -> Option<&'a PriceInfo> {
+fn find_price_info<'a>(coin: &str, prices: &'a [PriceInfo], currency: &Currency) -> Option<&'a PriceInfo> {
     prices
         .iter()
         .find(|price| price.coin == coin && price.currency == *currency)
         //.map(|price| price.value)
 }
 
+fn find_ath_info<'a>(coin: &str, ath_data: &'a [AthInfo], currency: &Currency) -> Option<&'a AthInfo> {
+    ath_data.iter().find(|info| info.coin == coin && info.currency == *currency)
+}
+
 pub fn list_wallets(wallets_data: &WalletsData) -> Vec<ListWalletLine> {
     
     let mut wallets = Vec::new();
@@ -237,6 +347,7 @@ pub fn list_wallets(wallets_data: &WalletsData) -> Vec<ListWalletLine> {
             wallet_name: wallet.name.clone(),
             wallet_kind: wallet.kind.clone(),
             wallet_address: wallet.address.clone(),
+            holdings: wallet.holdings.len(),
         };
         wallets.push(line);
     }
@@ -244,34 +355,3 @@ pub fn list_wallets(wallets_data: &WalletsData) -> Vec<ListWalletLine> {
     wallets
 
 }
-
-pub fn write_wallets_report(report_lines: &Vec<ListWalletLine>) {
-    println!(
-        "-----------------------------------------------------------------------------------------------"
-    );
-    println!(
-        "{:32} | {:32} | {:64}",
-        "Wallet",
-        "Kind",
-        "Address"
-    );
-    println!(
-        "-----------------------------------------------------------------------------------------------"
-    );
-
-    for line in report_lines {
-        println!(
-            "{:32} | {:32} | {:64}",
-            line.wallet_name,
-            line.wallet_kind,
-            line.wallet_address
-        );
-    }
-
-    println!(
-        "---------------------------------------------------------------------------------------------"
-    );
-    println!(
-        "-----------------------------------------------------------------------------------------------"
-    );
-}
@@ -0,0 +1,547 @@
+use std::io::{ self, Write };
+
+use crate::coins::AthSnapshot;
+use crate::model::OutputFormat;
+use crate::Result;
+
+use super::report::{ colored_change_percent, colored_value_change, readable_mkt_cap };
+use super::{ AllocationSummary, HoldingsReport, ReportLine, WalletsReport };
+
+const HOLDINGS_CSV_HEADER: [&str; 15] = [
+    "token",
+    "amount",
+    "value",
+    "allocation",
+    "mkt_cap",
+    "change_24h",
+    "wallet_name",
+    "wallet_kind",
+    "wallet_address",
+    "group",
+    "ath",
+    "from_ath",
+    "atl",
+    "ath_date",
+    "value_at_ath",
+];
+
+const GROUP_SEPARATOR: &str = " / ";
+
+const WALLETS_CSV_HEADER: [&str; 4] = ["wallet_name", "wallet_kind", "wallet_address", "holdings"];
+
+const ATH_HISTORY_CSV_HEADER: [&str; 6] = ["timestamp", "coin", "ath", "ath_date", "atl", "atl_date"];
+
+const HISTORY_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+pub fn write_report(report: &HoldingsReport, format: &OutputFormat) -> Result<()> {
+    let mut out = io::stdout().lock();
+    render_report(&mut out, report, format)
+}
+
+pub fn write_wallets_report(report: &WalletsReport, format: &OutputFormat) -> Result<()> {
+    let mut out = io::stdout().lock();
+    render_wallets_report(&mut out, report, format)
+}
+
+pub fn write_ath_history(history: &[AthSnapshot], format: &OutputFormat) -> Result<()> {
+    let mut out = io::stdout().lock();
+    render_ath_history(&mut out, history, format)
+}
+
+pub fn render_report(out: &mut impl Write, report: &HoldingsReport, format: &OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => render_report_table(out, report)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => {
+            if report.summary.is_some() {
+                return Err("Summary cannot be written as csv, use table, json or markdown output".into());
+            }
+            writeln!(out, "{}", HOLDINGS_CSV_HEADER.join(","))?;
+            for line in report.lines.iter() {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                    csv_field(&line.token),
+                    line.amount,
+                    line.value,
+                    line.allocation,
+                    line.mkt_cap,
+                    line.change_24h,
+                    csv_field(&line.wallet_name),
+                    csv_field(&line.wallet_kind),
+                    csv_field(&line.wallet_address),
+                    csv_field(&line.group.join(GROUP_SEPARATOR)),
+                    csv_option(line.ath),
+                    csv_option(line.from_ath),
+                    csv_option(line.atl),
+                    csv_field(line.ath_date.as_deref().unwrap_or_default()),
+                    csv_option(line.value_at_ath)
+                )?;
+            }
+        }
+        OutputFormat::Markdown => {
+            let currency = report.currency.to_uppercase_str();
+            let with_ath = report.has_ath_data();
+            writeln!(
+                out,
+                "| Token | Amount | Value ({}) | Alloc % | Mkt.Cap | 24H % | Wallet | Kind | Address |{}",
+                currency,
+                if with_ath { " ATH | From ATH | ATL | ATH date |" } else { "" }
+            )?;
+            writeln!(
+                out,
+                "|---|---:|---:|---:|---:|---:|---|---|---|{}",
+                if with_ath { "---:|---:|---:|---|" } else { "" }
+            )?;
+            // Rows without all time data have empty ATH cells, so every row has the columns of the header
+            let empty_ath_cells = if with_ath { " | | | |" } else { "" };
+            for (index, line) in report.lines.iter().enumerate() {
+                let ath_cells = if with_ath {
+                    format!(" {} |", ath_cells(line).join(" | "))
+                } else {
+                    String::new()
+                };
+                writeln!(
+                    out,
+                    "| {} | {:.6} | {:.2} | {:.2}% | {} | {:.2}% | {} | {} | {} |{}",
+                    markdown_field(&line.token),
+                    line.amount,
+                    line.value,
+                    line.allocation,
+                    readable_mkt_cap(line.mkt_cap),
+                    line.change_24h,
+                    markdown_field(&line.wallet_name),
+                    markdown_field(&line.wallet_kind),
+                    markdown_field(&line.wallet_address),
+                    ath_cells
+                )?;
+                for subtotal in closing_subtotals(report, index) {
+                    writeln!(
+                        out,
+                        "| **Subtotal {}** | | **{:.2}** | {:.2}% | | {:.2}% | | | |{}",
+                        markdown_field(&subtotal.group.join(GROUP_SEPARATOR)),
+                        subtotal.value,
+                        subtotal.allocation,
+                        subtotal.change_24h,
+                        empty_ath_cells
+                    )?;
+                }
+            }
+            writeln!(
+                out,
+                "| **Total** | {:.6} | **{:.2}** | | | | | | |{}",
+                report.totals.amount,
+                report.totals.value,
+                empty_ath_cells
+            )?;
+            if let Some(value_at_ath) = report.totals.value_at_ath {
+                writeln!(out, "| **Value at ATH** | | **{:.2}** | | | | | | |{}", value_at_ath, empty_ath_cells)?;
+            }
+            if let Some(summary) = report.summary.as_ref() {
+                render_summary_markdown(out, summary)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn render_wallets_report(out: &mut impl Write, report: &WalletsReport, format: &OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => render_wallets_table(out, report)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{}", WALLETS_CSV_HEADER.join(","))?;
+            for line in report.wallets.iter() {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    csv_field(&line.wallet_name),
+                    csv_field(&line.wallet_kind),
+                    csv_field(&line.wallet_address),
+                    line.holdings
+                )?;
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "| Wallet | Kind | Address | Holdings |")?;
+            writeln!(out, "|---|---|---|---:|")?;
+            for line in report.wallets.iter() {
+                writeln!(
+                    out,
+                    "| {} | {} | {} | {} |",
+                    markdown_field(&line.wallet_name),
+                    markdown_field(&line.wallet_kind),
+                    markdown_field(&line.wallet_address),
+                    line.holdings
+                )?;
+            }
+            writeln!(
+                out,
+                "| **Total: {} wallets** | | | {} |",
+                report.totals.wallets,
+                report.totals.holdings
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn render_ath_history(out: &mut impl Write, history: &[AthSnapshot], format: &OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => {
+            writeln!(out, "{:16} | {:14} | {:10} | {:14} | {:10}", "Updated", "ATH", "ATH date", "ATL", "ATL date")?;
+            writeln!(out, "--------------------------------------------------------------------------")?;
+            for snapshot in history.iter() {
+                writeln!(
+                    out,
+                    "{:16} | {:14.2} | {:10} | {:14.2} | {:10}",
+                    snapshot.timestamp.format(HISTORY_TIMESTAMP_FORMAT),
+                    snapshot.ath.ath,
+                    date_part(&snapshot.ath.ath_date),
+                    snapshot.ath.atl,
+                    date_part(&snapshot.ath.atl_date)
+                )?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, history)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{}", ATH_HISTORY_CSV_HEADER.join(","))?;
+            for snapshot in history.iter() {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    snapshot.timestamp.format(HISTORY_TIMESTAMP_FORMAT),
+                    csv_field(&snapshot.ath.coin),
+                    snapshot.ath.ath,
+                    csv_field(&snapshot.ath.ath_date),
+                    snapshot.ath.atl,
+                    csv_field(&snapshot.ath.atl_date)
+                )?;
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(out, "| Updated | ATH | ATH date | ATL | ATL date |")?;
+            writeln!(out, "|---|---:|---|---:|---|")?;
+            for snapshot in history.iter() {
+                writeln!(
+                    out,
+                    "| {} | {:.2} | {} | {:.2} | {} |",
+                    snapshot.timestamp.format(HISTORY_TIMESTAMP_FORMAT),
+                    snapshot.ath.ath,
+                    date_part(&snapshot.ath.ath_date),
+                    snapshot.ath.atl,
+                    date_part(&snapshot.ath.atl_date)
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn render_report_table(out: &mut impl Write, report: &HoldingsReport) -> io::Result<()> {
+    let grouped = report.lines.iter().any(|line| !line.group.is_empty());
+    let with_ath = report.has_ath_data();
+    writeln!(
+        out,
+        "-------------------------------------------------------------------------------------------------------"
+    )?;
+    write!(
+        out,
+        "{:8}| {:14} | {:12} | {:8} | {:10} | {:10} | {:20} | {:32}",
+        "Token",
+        "Amount",
+        "Value",
+        "Alloc %",
+        "Mkt.Cap",
+        "24H %",
+        "Wallet",
+        if grouped { "Group" } else { "Address" }
+    )?;
+    if with_ath {
+        write!(out, " | {:>14} | {:>9} | {:>14} | {:10}", "ATH", "From ATH", "ATL", "ATH date")?;
+    }
+    if report.changes.is_some() {
+        write!(out, " | {:>12}", "Change")?;
+    }
+    writeln!(out)?;
+    writeln!(
+        out,
+        "-------------------------------------------------------------------------------------------------------"
+    )?;
+
+    for (index, line) in report.lines.iter().enumerate() {
+        write!(
+            out,
+            "{:8}| {:14.6} | {:12.2} | {:>7.2}% | {:10} | {:10} | {:20} | {:32}",
+            line.token,
+            line.amount,
+            line.value,
+            line.allocation,
+            readable_mkt_cap(line.mkt_cap),
+            colored_change_percent(line.change_24h),
+            line.wallet_name,
+            if grouped { line.group.join(GROUP_SEPARATOR) } else { line.wallet_address.clone() }
+        )?;
+        if with_ath {
+            let [ath, from_ath, atl, ath_date] = ath_cells(line);
+            write!(out, " | {:>14} | {:>9} | {:>14} | {:10}", ath, from_ath, atl, ath_date)?;
+        }
+        if let Some(changes) = report.changes.as_ref() {
+            write!(out, " | {:>12}", colored_value_change(changes.lines[index]))?;
+        }
+        writeln!(out)?;
+        for subtotal in closing_subtotals(report, index) {
+            writeln!(
+                out,
+                "{:8}| {:14} | {:12.2} | {:>7.2}% | {:10} | {:10} | {:20} | {:32}",
+                "Subtotal",
+                "",
+                subtotal.value,
+                subtotal.allocation,
+                "",
+                colored_change_percent(subtotal.change_24h),
+                "",
+                subtotal.group.join(GROUP_SEPARATOR)
+            )?;
+        }
+    }
+
+    writeln!(
+        out,
+        "-----------------------------------------------------------------------------------------------------"
+    )?;
+    writeln!(out, "Amount  | {:14.6} |", report.totals.amount)?;
+    match report.changes.as_ref() {
+        Some(changes) => writeln!(out, "Sum     | {:14.2} | {}", report.totals.value, colored_value_change(Some(changes.total)))?,
+        None => writeln!(out, "Sum     | {:14.2} |", report.totals.value)?,
+    }
+    if let Some(value_at_ath) = report.totals.value_at_ath {
+        writeln!(out, "At ATH  | {:14.2} |", value_at_ath)?;
+    }
+    writeln!(
+        out,
+        "-------------------------------------------------------------------------------------------------------"
+    )?;
+
+    if let Some(summary) = report.summary.as_ref() {
+        render_summary_table(out, summary)?;
+    }
+    Ok(())
+}
+
+fn render_summary_table(out: &mut impl Write, summary: &AllocationSummary) -> io::Result<()> {
+    writeln!(out, "Top {} tokens      | {:>7.2}%", summary.top_n, summary.top_n_share)?;
+    for group in summary.top_tokens.iter() {
+        writeln!(out, "  {:16}| {:>7.2}% | {:14.2}", group.name, group.allocation, group.value)?;
+    }
+    writeln!(out, "Herfindahl index   | {:8.4}", summary.herfindahl_index)?;
+    writeln!(out, "Stablecoin share   | {:>7.2}%", summary.stablecoin_share)?;
+
+    if let Some(breakdown_by) = summary.breakdown_by.as_ref() {
+        writeln!(out, "Allocation by {:?}", breakdown_by)?;
+        for group in summary.breakdown.iter() {
+            writeln!(out, "  {:16}| {:>7.2}% | {:14.2}", group.name, group.allocation, group.value)?;
+        }
+    }
+    writeln!(
+        out,
+        "-------------------------------------------------------------------------------------------------------"
+    )?;
+    Ok(())
+}
+
+fn render_summary_markdown(out: &mut impl Write, summary: &AllocationSummary) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "| Metric | Value |")?;
+    writeln!(out, "|---|---:|")?;
+    writeln!(out, "| Top {} tokens share | {:.2}% |", summary.top_n, summary.top_n_share)?;
+    writeln!(out, "| Herfindahl index | {:.4} |", summary.herfindahl_index)?;
+    writeln!(out, "| Stablecoin share | {:.2}% |", summary.stablecoin_share)?;
+
+    if !summary.breakdown.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "| Group | Value | Alloc % |")?;
+        writeln!(out, "|---|---:|---:|")?;
+        for group in summary.breakdown.iter() {
+            writeln!(out, "| {} | {:.2} | {:.2}% |", markdown_field(&group.name), group.value, group.allocation)?;
+        }
+    }
+    Ok(())
+}
+
+fn render_wallets_table(out: &mut impl Write, report: &WalletsReport) -> io::Result<()> {
+    writeln!(
+        out,
+        "-----------------------------------------------------------------------------------------------"
+    )?;
+    writeln!(out, "{:32} | {:32} | {:64}", "Wallet", "Kind", "Address")?;
+    writeln!(
+        out,
+        "-----------------------------------------------------------------------------------------------"
+    )?;
+
+    for line in report.wallets.iter() {
+        writeln!(
+            out,
+            "{:32} | {:32} | {:64}",
+            line.wallet_name,
+            line.wallet_kind,
+            line.wallet_address
+        )?;
+    }
+
+    writeln!(
+        out,
+        "-----------------------------------------------------------------------------------------------"
+    )?;
+    Ok(())
+}
+
+// Returns the subtotals of the groups which end with the line on the index, from the innermost group
+fn closing_subtotals(report: &HoldingsReport, index: usize) -> Vec<&ReportLine> {
+    let line = &report.lines[index];
+    let next = report.lines.get(index + 1);
+
+    (1..line.group.len())
+        .rev()
+        .filter(|depth| next.is_none_or(|next| next.group.get(..*depth) != line.group.get(..*depth)))
+        .filter_map(|depth| report.subtotals.iter().find(|subtotal| subtotal.group[..] == line.group[..depth]))
+        .collect()
+}
+
+// Returns ATH, distance from ATH, ATL and ATH date of the line, empty when the line has no ATH data
+fn ath_cells(line: &ReportLine) -> [String; 4] {
+    [
+        line.ath.map(|ath| format!("{:.2}", ath)).unwrap_or_default(),
+        line.from_ath.map(|from_ath| format!("{:.2}%", from_ath)).unwrap_or_default(),
+        line.atl.map(|atl| format!("{:.2}", atl)).unwrap_or_default(),
+        line.ath_date.as_deref().map(date_part).unwrap_or_default(),
+    ]
+}
+
+// Returns only the date part of the timestamp from the provider - 2021-11-10T14:24:11.849Z
+fn date_part(timestamp: &str) -> String {
+    timestamp.get(..10).unwrap_or(timestamp).to_string()
+}
+
+fn csv_option(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+// Quotes the field when it contains a separator, quote or new line
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn markdown_field(value: &str) -> String {
+    value.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Currency;
+
+    fn sample_report() -> HoldingsReport {
+        let lines = vec![
+            ReportLine {
+                token: "BTC".to_string(),
+                amount: 0.5,
+                value: 50000.0,
+                allocation: 86.2,
+                mkt_cap: 2_000_000_000_000.0,
+                change_24h: 1.5,
+                wallet_name: "Ledger, main".to_string(),
+                wallet_kind: "Ledger".to_string(),
+                wallet_address: "bc1q".to_string(),
+                ..Default::default()
+            },
+            ReportLine {
+                token: "ETH".to_string(),
+                amount: 2.0,
+                value: 8000.0,
+                allocation: 13.8,
+                mkt_cap: 400_000_000_000.0,
+                change_24h: -2.0,
+                wallet_name: "Meta|Mask".to_string(),
+                wallet_kind: "MetaMask".to_string(),
+                wallet_address: "0xabc".to_string(),
+                ..Default::default()
+            }
+        ];
+        HoldingsReport::new(lines, Currency::USD)
+    }
+
+    fn render_to_string(report: &HoldingsReport, format: &OutputFormat) -> String {
+        let mut out = Vec::new();
+        render_report(&mut out, report, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_render_report_json() {
+        let output = render_to_string(&sample_report(), &OutputFormat::Json);
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(json["currency"], "USD");
+        assert_eq!(json["lines"].as_array().unwrap().len(), 2);
+        assert_eq!(json["totals"]["value"], 58000.0);
+        assert_eq!(json["totals"]["lines"], 2);
+    }
+
+    #[test]
+    fn test_render_report_csv() {
+        let output = render_to_string(&sample_report(), &OutputFormat::Csv);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], HOLDINGS_CSV_HEADER.join(","));
+        assert_eq!(lines[1], "BTC,0.5,50000,86.2,2000000000000,1.5,\"Ledger, main\",Ledger,bc1q,,,,,,");
+
+        let report = sample_report();
+        let summary = crate::service::allocation_summary(&report.lines, &crate::config::coins::CoinsData { coins: Vec::new() }, 5, None);
+        assert!(render_report(&mut Vec::new(), &report.with_summary(summary), &OutputFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_render_report_markdown() {
+        let output = render_to_string(&sample_report(), &OutputFormat::Markdown);
+        assert!(output.contains("Value (USD)"));
+        assert!(output.contains("Meta\\|Mask"));
+        assert!(output.lines().last().unwrap().contains("**58000.00**"));
+
+        let mut report = sample_report();
+        report.lines[0].ath = Some(69000.0);
+        report.lines[0].value_at_ath = Some(34500.0);
+        let report = HoldingsReport::new(report.lines, Currency::USD);
+        let output = render_to_string(&report, &OutputFormat::Markdown);
+        // 13 columns have 14 separators, escaped pipes in the values are not separators
+        let separators = |line: &str| line.matches('|').count() - line.matches("\\|").count();
+        assert!(output.lines().all(|line| separators(line) == 14), "{}", output);
+    }
+
+    #[test]
+    fn test_render_report_table_changes() {
+        let mut previous = sample_report();
+        previous.lines.truncate(1);
+        previous.lines[0].value = 49000.0;
+        previous.totals.value = 49000.0;
+        let output = render_to_string(&sample_report().with_changes(&previous), &OutputFormat::Table);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[1].ends_with("Change"), "{}", output);
+        assert!(lines[3].contains("▲   +1000.00"), "{}", output);
+        assert!(lines[4].ends_with("new"), "{}", output);
+        assert!(output.contains("▲   +9000.00"), "{}", output);
+    }
+}
@@ -18,4 +18,14 @@ pub fn colored_change_percent(value: f64) -> ColoredString {
     } else {
         format!("{:>7.2}%", value).red()
     }
-}
\ No newline at end of file
+}
+
+// Value change since the previous refresh, with an arrow showing the direction
+pub fn colored_value_change(change: Option<f64>) -> ColoredString {
+    match change {
+        None => format!("{:>12}", "new").normal(),
+        Some(change) if change.abs() < 0.005 => format!("{:>12}", "-").normal(),
+        Some(change) if change > 0.0 => format!("▲ {:>+10.2}", change).green(),
+        Some(change) => format!("▼ {:>+10.2}", change).red(),
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::config::coins::CoinsData;
+use crate::model::AllocationBy;
+
+use super::ReportLine;
+
+const UNCATEGORIZED: &str = "uncategorized";
+
+// Share of a group in the portfolio, allocation is in percent
+#[derive(Debug, Serialize)]
+pub struct AllocationGroup {
+    pub name: String,
+    pub value: f64,
+    pub allocation: f64,
+}
+
+// Concentration metrics of the portfolio, shares are in percent.
+// Herfindahl index is the sum of squared token shares (0..1), 1 means everything is in one token.
+#[derive(Debug, Serialize)]
+pub struct AllocationSummary {
+    pub top_n: usize,
+    pub top_n_share: f64,
+    pub top_tokens: Vec<AllocationGroup>,
+    pub herfindahl_index: f64,
+    pub stablecoin_share: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown_by: Option<AllocationBy>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub breakdown: Vec<AllocationGroup>,
+}
+
+// Computes the concentration metrics from the report lines.
+// Lines of the same token are summed first, so the metrics do not depend on grouping of the report.
+// In the breakdown by category, a token with more categories is counted in each of them.
+pub fn allocation_summary(
+    lines: &[ReportLine],
+    coins_data: &CoinsData,
+    top_n: usize,
+    breakdown_by: Option<&AllocationBy>
+) -> AllocationSummary {
+    let total: f64 = lines.iter().map(|line| line.value).sum();
+
+    let tokens = sum_by(lines.iter().map(|line| (line.token.clone(), line.value)), total);
+    let herfindahl_index = tokens
+        .iter()
+        .map(|group| (group.allocation / 100.0).powi(2))
+        .sum();
+
+    let top_tokens: Vec<AllocationGroup> = tokens.into_iter().take(top_n).collect();
+    let top_n_share = top_tokens.iter().map(|group| group.allocation).sum();
+
+    let stablecoin_value: f64 = lines
+        .iter()
+        .filter(|line| coins_data.is_stablecoin(&line.token))
+        .map(|line| line.value)
+        .sum();
+
+    let breakdown = match breakdown_by {
+        None => Vec::new(),
+        Some(AllocationBy::Kind) => {
+            sum_by(lines.iter().map(|line| (line.wallet_kind.clone(), line.value)), total)
+        }
+        Some(AllocationBy::Category) => {
+            let values = lines.iter().flat_map(|line| {
+                let categories = coins_data.get_categories(&line.token);
+                if categories.is_empty() {
+                    vec![(UNCATEGORIZED.to_string(), line.value)]
+                } else {
+                    categories
+                        .iter()
+                        .map(|category| (category.clone(), line.value))
+                        .collect()
+                }
+            });
+            sum_by(values, total)
+        }
+    };
+
+    AllocationSummary {
+        top_n,
+        top_n_share,
+        top_tokens,
+        herfindahl_index,
+        stablecoin_share: percent(stablecoin_value, total),
+        breakdown_by: breakdown_by.cloned(),
+        breakdown,
+    }
+}
+
+// Adding 0.0 turns -0.0 (the sum of no values) into 0.0, so it is not printed as -0.00%
+pub fn percent(value: f64, total: f64) -> f64 {
+    if total > 0.0 { (value / total) * 100.0 + 0.0 } else { 0.0 }
+}
+
+// Sums the values by name, ordered from the largest
+fn sum_by<I>(values: I, total: f64) -> Vec<AllocationGroup> where I: Iterator<Item = (String, f64)> {
+    let mut grouped: HashMap<String, f64> = HashMap::new();
+    for (name, value) in values {
+        *grouped.entry(name).or_default() += value;
+    }
+
+    let mut groups: Vec<AllocationGroup> = grouped
+        .into_iter()
+        .map(|(name, value)| AllocationGroup {
+            name,
+            value,
+            allocation: percent(value, total),
+        })
+        .collect();
+    groups.sort_by(|a, b| b.value.total_cmp(&a.value).then_with(|| a.name.cmp(&b.name)));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::coins::CoinDef;
+
+    fn line(token: &str, kind: &str, value: f64) -> ReportLine {
+        ReportLine {
+            token: token.to_string(),
+            amount: 1.0,
+            value,
+            allocation: 0.0,
+            mkt_cap: 0.0,
+            change_24h: 0.0,
+            wallet_name: "wallet".to_string(),
+            wallet_kind: kind.to_string(),
+            wallet_address: "-".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn coin_def(code: &str, categories: &[&str]) -> CoinDef {
+        serde_json::from_value(serde_json::json!({
+            "code": code,
+            "categories": categories,
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_allocation_summary() {
+        let coins_data = CoinsData {
+            coins: vec![
+                coin_def("BTC", &["L1"]),
+                coin_def("ETH", &["L1", "DeFi"]),
+                coin_def("USDC", &["Stablecoin"])
+            ],
+        };
+        let lines = vec![
+            line("BTC", "Ledger", 40.0),
+            line("BTC", "Exchange", 20.0),
+            line("ETH", "MetaMask", 30.0),
+            line("USDC", "Exchange", 10.0)
+        ];
+
+        let summary = allocation_summary(&lines, &coins_data, 2, Some(&AllocationBy::Category));
+
+        assert_eq!(summary.top_tokens[0].name, "BTC");
+        assert!((summary.top_n_share - 90.0).abs() < 1e-9);
+        assert!((summary.herfindahl_index - (0.36 + 0.09 + 0.01)).abs() < 1e-9);
+        assert!((summary.stablecoin_share - 10.0).abs() < 1e-9);
+        assert_eq!(summary.breakdown[0].name, "L1");
+        assert!((summary.breakdown[0].allocation - 90.0).abs() < 1e-9);
+        assert!(summary.breakdown.iter().any(|group| group.name == "DeFi" && group.value == 30.0));
+
+        let summary = allocation_summary(&lines, &coins_data, 2, Some(&AllocationBy::Kind));
+        assert_eq!(summary.breakdown[0].name, "Ledger");
+        assert_eq!(summary.breakdown[1].name, "Exchange");
+        assert_eq!(summary.breakdown[1].value, 30.0);
+
+        let summary = allocation_summary(&lines[..3], &coins_data, 2, None);
+        assert!(summary.stablecoin_share.is_sign_positive());
+    }
+}
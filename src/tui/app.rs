@@ -0,0 +1,334 @@
+use chrono::{ DateTime, Local };
+use clap::ValueEnum;
+use ratatui::crossterm::event::{ KeyCode, KeyEvent, KeyModifiers };
+use ratatui::widgets::TableState;
+
+use crate::coins::PricesSnapshot;
+use crate::config::wallets::WalletsData;
+use crate::model::{ AthInfo, Currency, GroupBy, PriceInfo, ReportOrder, ReportSortBy };
+use crate::service::{ report_holdings, ReportFilter, ReportLine };
+
+// Sort columns of the holdings table by the number keys
+pub const SORT_KEYS: [(char, ReportSortBy); 6] = [
+    ('1', ReportSortBy::Token),
+    ('2', ReportSortBy::Amount),
+    ('3', ReportSortBy::Value),
+    ('4', ReportSortBy::MktCap),
+    ('5', ReportSortBy::Change24h),
+    ('6', ReportSortBy::FromAth),
+];
+
+const PAGE_SIZE: usize = 10;
+
+// Wallets, prices and all time data shown in the dashboard, reloaded at every refresh
+pub struct PortfolioData {
+    pub wallets: WalletsData,
+    pub prices: Vec<PriceInfo>,
+    pub ath_data: Vec<AthInfo>,
+    pub history: Vec<PricesSnapshot>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    Holdings,
+    Wallets,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    None,
+    Quit,
+    Refresh,
+}
+
+pub struct WalletRow {
+    pub name: String,
+    pub kind: String,
+    pub holdings: usize,
+    pub value: f64,
+}
+
+// Details of the token selected in the holdings table
+pub struct TokenDetail<'a> {
+    pub token: String,
+    pub price: Option<&'a PriceInfo>,
+    pub ath: Option<&'a AthInfo>,
+    // Amount and value in all wallets
+    pub amount: f64,
+    pub value: f64,
+    pub history: Vec<f64>,
+}
+
+pub struct App {
+    pub data: PortfolioData,
+    pub filter: ReportFilter,
+    // Typed filter of the token and wallet names
+    pub search: String,
+    pub searching: bool,
+    pub lines: Vec<ReportLine>,
+    pub wallets: Vec<WalletRow>,
+    pub holdings_state: TableState,
+    pub wallets_state: TableState,
+    pub focus: Focus,
+    pub updated: Option<DateTime<Local>>,
+    pub refreshing: bool,
+    // Last refresh error or other message for the status bar
+    pub message: Option<String>,
+}
+
+impl App {
+    pub fn new(data: PortfolioData, currency: Currency) -> Self {
+        let filter = ReportFilter { currency, ..ReportFilter::default() };
+        let mut app = App {
+            data,
+            filter,
+            search: String::new(),
+            searching: false,
+            lines: Vec::new(),
+            wallets: Vec::new(),
+            holdings_state: TableState::default(),
+            wallets_state: TableState::default(),
+            focus: Focus::Holdings,
+            updated: Some(Local::now()),
+            refreshing: false,
+            message: None,
+        };
+        app.rebuild();
+        app
+    }
+
+    pub fn set_data(&mut self, data: PortfolioData) {
+        self.data = data;
+        self.updated = Some(Local::now());
+        self.message = None;
+        self.rebuild();
+    }
+
+    // Rebuilds the tables after a change of the data, filter or sorting. The selected token stays selected.
+    pub fn rebuild(&mut self) {
+        let selected = self.selected_line().map(|line| (line.token.clone(), line.wallet_name.clone()));
+
+        let search = self.search.to_lowercase();
+        self.lines = report_holdings(&self.data.wallets, &self.data.prices, &self.data.ath_data, &self.filter);
+        self.lines.retain(|line| {
+            search.is_empty() ||
+                line.token.to_lowercase().contains(&search) ||
+                line.wallet_name.to_lowercase().contains(&search)
+        });
+        let index = selected
+            .and_then(|(token, wallet)| self.lines.iter().position(|line| line.token == token && line.wallet_name == wallet))
+            .unwrap_or(0);
+        self.holdings_state.select((!self.lines.is_empty()).then_some(index));
+
+        self.wallets = self.wallet_rows();
+        let index = self.wallets_state.selected().unwrap_or(0).min(self.wallets.len().saturating_sub(1));
+        self.wallets_state.select((!self.wallets.is_empty()).then_some(index));
+    }
+
+    fn wallet_rows(&self) -> Vec<WalletRow> {
+        self.data.wallets.wallets
+            .iter()
+            .map(|wallet| WalletRow {
+                name: wallet.name.clone(),
+                kind: wallet.kind.clone(),
+                holdings: wallet.holdings.len(),
+                value: wallet.holdings
+                    .iter()
+                    .filter_map(|holding| self.price(&holding.coin).map(|price| holding.amount * price.value))
+                    .sum(),
+            })
+            .collect()
+    }
+
+    fn price(&self, coin: &str) -> Option<&PriceInfo> {
+        self.data.prices.iter().find(|price| price.coin == coin && price.currency == self.filter.currency)
+    }
+
+    pub fn selected_line(&self) -> Option<&ReportLine> {
+        self.holdings_state.selected().and_then(|index| self.lines.get(index))
+    }
+
+    pub fn token_detail(&self) -> Option<TokenDetail<'_>> {
+        let token = self.selected_line()?.token.clone();
+        let currency = &self.filter.currency;
+        let amount: f64 = self.data.wallets.wallets
+            .iter()
+            .flat_map(|wallet| wallet.holdings.iter())
+            .filter(|holding| holding.coin == token)
+            .map(|holding| holding.amount)
+            .sum();
+        let price = self.price(&token);
+        let history = self.data.history
+            .iter()
+            .filter_map(|snapshot| snapshot.prices.iter().find(|price| price.coin == token && price.currency == *currency))
+            .map(|price| price.value)
+            .collect();
+
+        Some(TokenDetail {
+            price,
+            ath: self.data.ath_data.iter().find(|info| info.coin == token && info.currency == *currency),
+            amount,
+            value: price.map_or(0.0, |price| amount * price.value),
+            history,
+            token,
+        })
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        if self.searching {
+            match key.code {
+                KeyCode::Char(c) => self.search.push(c),
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.searching = false;
+                }
+                _ => return Action::None,
+            }
+            self.rebuild();
+            return Action::None;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Esc if self.search.is_empty() => return Action::Quit,
+            KeyCode::Esc => {
+                self.search.clear();
+                self.rebuild();
+            }
+            KeyCode::Char('r') => return Action::Refresh,
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Holdings => Focus::Wallets,
+                    Focus::Wallets => Focus::Holdings,
+                };
+            }
+            KeyCode::Char('o') => {
+                self.filter.order = match self.filter.order {
+                    ReportOrder::Asc => ReportOrder::Desc,
+                    ReportOrder::Desc => ReportOrder::Asc,
+                };
+                self.rebuild();
+            }
+            KeyCode::Char('g') => {
+                self.filter.group_by = if self.filter.group_by.is_empty() { vec![GroupBy::Token] } else { Vec::new() };
+                self.rebuild();
+            }
+            KeyCode::Char('c') => {
+                let currencies = Currency::value_variants();
+                let index = currencies.iter().position(|currency| *currency == self.filter.currency).unwrap_or(0);
+                self.filter.currency = currencies[(index + 1) % currencies.len()].clone();
+                self.rebuild();
+            }
+            KeyCode::Char(c) if SORT_KEYS.iter().any(|(key, _)| *key == c) => {
+                let sort_by = SORT_KEYS.iter().find(|(key, _)| *key == c).map(|(_, sort_by)| sort_by.clone()).unwrap_or(ReportSortBy::Value);
+                self.set_sort(sort_by);
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.move_selection(PAGE_SIZE as isize),
+            KeyCode::PageUp => self.move_selection(-(PAGE_SIZE as isize)),
+            KeyCode::Home => self.move_selection(isize::MIN / 2),
+            KeyCode::End => self.move_selection(isize::MAX / 2),
+            _ => {}
+        }
+        Action::None
+    }
+
+    // The same column again changes the order, a new column starts descending - tokens ascending
+    fn set_sort(&mut self, sort_by: ReportSortBy) {
+        if self.filter.sort_by == sort_by {
+            self.filter.order = match self.filter.order {
+                ReportOrder::Asc => ReportOrder::Desc,
+                ReportOrder::Desc => ReportOrder::Asc,
+            };
+        } else {
+            self.filter.order = if sort_by == ReportSortBy::Token { ReportOrder::Asc } else { ReportOrder::Desc };
+            self.filter.sort_by = sort_by;
+        }
+        self.rebuild();
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        let (state, len) = match self.focus {
+            Focus::Holdings => (&mut self.holdings_state, self.lines.len()),
+            Focus::Wallets => (&mut self.wallets_state, self.wallets.len()),
+        };
+        if len == 0 {
+            return;
+        }
+        let index = state.selected().unwrap_or(0) as isize;
+        state.select(Some(index.saturating_add(offset).clamp(0, len as isize - 1) as usize));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(coin: &str, value: f64) -> PriceInfo {
+        PriceInfo { coin: coin.to_string(), currency: Currency::USD, value, market_cap: 0.0, change_24h: 0.0 }
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Action {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn tokens(app: &App) -> Vec<&str> {
+        app.lines.iter().map(|line| line.token.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_filter_and_select() {
+        let wallets: WalletsData = serde_json::from_value(serde_json::json!({
+            "wallets": [
+                { "name": "Ledger", "kind": "Ledger", "address": "a", "holdings": [
+                    { "coin": "BTC", "amount": 1.0 },
+                    { "coin": "ETH", "amount": 10.0 }
+                ] },
+                { "name": "MetaMask", "kind": "MetaMask", "address": "b", "holdings": [{ "coin": "ETH", "amount": 1.0 }] }
+            ]
+        })).unwrap();
+        let data = PortfolioData {
+            wallets,
+            prices: vec![price("BTC", 100000.0), price("ETH", 2000.0)],
+            ath_data: Vec::new(),
+            history: Vec::new(),
+        };
+        let mut app = App::new(data, Currency::USD);
+        assert_eq!(tokens(&app), vec!["BTC", "ETH", "ETH"]);
+
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.selected_line().unwrap().wallet_name, "Ledger");
+        press(&mut app, KeyCode::Char('3'));
+        assert_eq!(tokens(&app), vec!["ETH", "ETH", "BTC"]);
+        // The selected line stays selected after sorting
+        assert_eq!(app.holdings_state.selected(), Some(1));
+
+        press(&mut app, KeyCode::Char('/'));
+        for c in "mask".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!(press(&mut app, KeyCode::Char('q')), Action::None);
+        assert!(app.lines.is_empty());
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.search, "mask");
+        assert_eq!(tokens(&app), vec!["ETH"]);
+
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('g'));
+        assert_eq!(tokens(&app), vec!["ETH", "BTC"]);
+        let detail = app.token_detail().unwrap();
+        assert_eq!((detail.amount, detail.value), (11.0, 22000.0));
+        assert_eq!(app.wallets[0].value, 120000.0);
+        assert_eq!(press(&mut app, KeyCode::Char('q')), Action::Quit);
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::mpsc::{ self, Receiver };
+use std::time::{ Duration, Instant };
+
+use ratatui::crossterm::event::{ self, Event, KeyEventKind };
+use ratatui::DefaultTerminal;
+use tracing::{ info, warn };
+
+use crate::coins;
+use crate::config::app::read_default_app_config;
+use crate::config::coins::read_default_coins_config;
+use crate::config::paths::Paths;
+use crate::config::wallets::read_default_wallets_config;
+use crate::daemon::lock_data_dir;
+use crate::model::Currency;
+use crate::Result;
+
+use app::{ Action, App, PortfolioData };
+
+mod app;
+mod ui;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct TuiOptions {
+    pub currency: Currency,
+    // Seconds between the background refreshes
+    pub refresh: u64,
+    // Fetch the prices from the providers at the refresh, otherwise only the stored prices are reloaded
+    pub fetch: bool,
+}
+
+// Runs the dashboard until q or Ctrl-C. The data are loaded before the terminal is switched,
+// so the passphrase of encrypted configs can be entered.
+pub fn run_tui(paths: &Paths, options: &TuiOptions) -> Result<()> {
+    let mut app = App::new(load_data(paths)?, options.currency.clone());
+
+    // Fetching the prices writes into the data dir, so it is locked like by the daemon
+    let mut fetch = options.fetch;
+    let _lock = if fetch {
+        match lock_data_dir(paths) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                warn!(error = %e, "Prices are not fetched");
+                app.message = Some(format!("{}, prices are only reloaded", e));
+                fetch = false;
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut terminal = ratatui::init();
+    let result = run_loop(&mut terminal, &mut app, paths, options.refresh, fetch);
+    ratatui::restore();
+    result
+}
+
+fn run_loop(terminal: &mut DefaultTerminal, app: &mut App, paths: &Paths, refresh: u64, fetch: bool) -> Result<()> {
+    let refresh_every = Duration::from_secs(refresh.max(1));
+    let mut refresh_rx: Option<Receiver<std::result::Result<PortfolioData, String>>> = None;
+    // The first refresh fetches the prices right away, reloading only waits for the interval
+    let mut last_refresh: Option<Instant> = if fetch { None } else { Some(Instant::now()) };
+
+    loop {
+        if let Some(rx) = &refresh_rx {
+            match rx.try_recv() {
+                Ok(Ok(data)) => {
+                    app.set_data(data);
+                    refresh_rx = None;
+                }
+                Ok(Err(e)) => {
+                    app.message = Some(e);
+                    refresh_rx = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    app.message = Some("Refresh failed".to_string());
+                    refresh_rx = None;
+                }
+            }
+            app.refreshing = refresh_rx.is_some();
+        }
+
+        if refresh_rx.is_none() && last_refresh.is_none_or(|last| last.elapsed() >= refresh_every) {
+            refresh_rx = Some(start_refresh(paths, fetch));
+            app.refreshing = true;
+            last_refresh = Some(Instant::now());
+        }
+
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        if !event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match app.handle_key(key) {
+                Action::Quit => return Ok(()),
+                Action::Refresh if refresh_rx.is_none() => last_refresh = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+// Fetches the prices and reloads the data in a thread, so the dashboard stays responsive
+fn start_refresh(paths: &Paths, fetch: bool) -> Receiver<std::result::Result<PortfolioData, String>> {
+    let (tx, rx) = mpsc::channel();
+    let paths = paths.clone();
+    std::thread::spawn(move || {
+        let result = refresh(&paths, fetch).map_err(|e| e.to_string());
+        if let Err(e) = &result {
+            warn!(error = %e, "Refresh failed");
+        }
+        tx.send(result).ok();
+    });
+    rx
+}
+
+fn refresh(paths: &Paths, fetch: bool) -> Result<PortfolioData> {
+    if fetch {
+        let app_config = read_default_app_config(paths)?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        runtime.block_on(coins::update_prices::update_coins_prices(paths, &app_config.currencies))?;
+        info!("Prices updated");
+    }
+    load_data(paths)
+}
+
+fn load_data(paths: &Paths) -> Result<PortfolioData> {
+    let coins_data = read_default_coins_config(paths)?;
+    Ok(PortfolioData {
+        wallets: read_default_wallets_config(paths)?.resolve_aliases(&coins_data),
+        prices: coins::get_latest_prices(paths)?,
+        ath_data: coins::get_ath_data(paths)?,
+        history: coins::get_prices_history(paths)?,
+    })
+}
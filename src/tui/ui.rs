@@ -0,0 +1,201 @@
+use ratatui::layout::{ Constraint, Layout, Rect };
+use ratatui::style::{ Color, Modifier, Style, Stylize };
+use ratatui::text::{ Line, Span };
+use ratatui::widgets::{ Block, Borders, Paragraph, Row, Sparkline, Table };
+use ratatui::Frame;
+
+use crate::model::{ ReportOrder, ReportSortBy };
+use crate::service::readable_mkt_cap;
+
+use super::app::{ App, Focus, TokenDetail, SORT_KEYS };
+
+const HISTORY_HEIGHT: u16 = 8;
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [main, status] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [left, right] = Layout::horizontal([Constraint::Percentage(68), Constraint::Percentage(32)]).areas(main);
+    let [holdings, wallets] = Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(left);
+    let [detail, history] = Layout::vertical([Constraint::Min(0), Constraint::Length(HISTORY_HEIGHT)]).areas(right);
+
+    draw_holdings(frame, app, holdings);
+    draw_wallets(frame, app, wallets);
+    match app.token_detail() {
+        Some(token_detail) => {
+            draw_detail(frame, app, &token_detail, detail);
+            draw_history(frame, &token_detail, history);
+        }
+        None => {
+            frame.render_widget(Paragraph::new("No holdings").block(Block::bordered().title(" Token ")), detail);
+            frame.render_widget(Block::bordered().title(" Price history "), history);
+        }
+    }
+    draw_status(frame, app, status);
+}
+
+fn pane_block(title: String, focused: bool) -> Block<'static> {
+    let style = if focused { Style::new().fg(Color::Cyan) } else { Style::new() };
+    Block::new().borders(Borders::ALL).border_style(style).title(title)
+}
+
+fn change_style(value: f64) -> Style {
+    if value > 0.0 { Style::new().fg(Color::Green) } else { Style::new().fg(Color::Red) }
+}
+
+// Column title with the number key for sorting and the order of the sorted column
+fn sort_title(app: &App, title: &str, sort_by: ReportSortBy) -> String {
+    let key = SORT_KEYS.iter().find(|(_, key_sort_by)| *key_sort_by == sort_by).map_or(' ', |(key, _)| *key);
+    if app.filter.sort_by != sort_by {
+        return format!("{} {}", title, key);
+    }
+    match app.filter.order {
+        ReportOrder::Asc => format!("{} {}▲", title, key),
+        ReportOrder::Desc => format!("{} {}▼", title, key),
+    }
+}
+
+fn draw_holdings(frame: &mut Frame, app: &mut App, area: Rect) {
+    let header = Row::new(vec![
+        sort_title(app, "Token", ReportSortBy::Token),
+        sort_title(app, "Amount", ReportSortBy::Amount),
+        sort_title(app, "Value", ReportSortBy::Value),
+        "Alloc".to_string(),
+        sort_title(app, "Mkt cap", ReportSortBy::MktCap),
+        sort_title(app, "24h", ReportSortBy::Change24h),
+        sort_title(app, "From ATH", ReportSortBy::FromAth),
+        "Wallet".to_string(),
+    ]).style(Style::new().add_modifier(Modifier::BOLD));
+
+    let rows = app.lines.iter().map(|line| {
+        Row::new(vec![
+            Line::from(line.token.clone()),
+            Line::from(format!("{:.4}", line.amount)).right_aligned(),
+            Line::from(format!("{:.2}", line.value)).right_aligned(),
+            Line::from(format!("{:.2}%", line.allocation)).right_aligned(),
+            Line::from(readable_mkt_cap(line.mkt_cap)).right_aligned(),
+            Line::styled(format!("{:.2}%", line.change_24h), change_style(line.change_24h)).right_aligned(),
+            Line::from(line.from_ath.map_or("-".to_string(), |from_ath| format!("{:.2}%", from_ath))).right_aligned(),
+            Line::from(line.wallet_name.clone()),
+        ])
+    });
+    let widths = [
+        Constraint::Length(8),
+        Constraint::Length(16),
+        Constraint::Length(14),
+        Constraint::Length(8),
+        Constraint::Length(11),
+        Constraint::Length(9),
+        Constraint::Length(11),
+        Constraint::Min(10),
+    ];
+
+    let total: f64 = app.lines.iter().map(|line| line.value).sum();
+    let title = format!(" Holdings - {} lines, {:.2} {} ", app.lines.len(), total, app.filter.currency.to_uppercase_str());
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(pane_block(title, app.focus == Focus::Holdings))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, area, &mut app.holdings_state);
+}
+
+fn draw_wallets(frame: &mut Frame, app: &mut App, area: Rect) {
+    let header = Row::new(vec!["Wallet", "Kind", "Holdings", "Value"]).style(Style::new().add_modifier(Modifier::BOLD));
+    let rows = app.wallets.iter().map(|wallet| {
+        Row::new(vec![
+            Line::from(wallet.name.clone()),
+            Line::from(wallet.kind.clone()),
+            Line::from(wallet.holdings.to_string()).right_aligned(),
+            Line::from(format!("{:.2}", wallet.value)).right_aligned(),
+        ])
+    });
+    let widths = [Constraint::Min(20), Constraint::Length(14), Constraint::Length(9), Constraint::Length(14)];
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(pane_block(format!(" Wallets - {} ", app.wallets.len()), app.focus == Focus::Wallets))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, area, &mut app.wallets_state);
+}
+
+fn draw_detail(frame: &mut Frame, app: &App, detail: &TokenDetail, area: Rect) {
+    let currency = app.filter.currency.to_uppercase_str();
+    let field = |name: &str, value: String| Line::from(vec![Span::from(format!("{:12}", name)).bold(), Span::from(value)]);
+
+    let mut lines = vec![
+        field("Amount", format!("{:.4}", detail.amount)),
+        field("Value", format!("{:.2} {}", detail.value, currency)),
+    ];
+    match detail.price {
+        Some(price) => {
+            lines.push(field("Price", format!("{:.4} {}", price.value, currency)));
+            lines.push(Line::from(vec![
+                Span::from(format!("{:12}", "24h change")).bold(),
+                Span::styled(format!("{:.2}%", price.change_24h), change_style(price.change_24h)),
+            ]));
+            lines.push(field("Mkt cap", readable_mkt_cap(price.market_cap)));
+        }
+        None => lines.push(field("Price", "-".to_string())),
+    }
+    lines.push(Line::default());
+    match detail.ath {
+        Some(ath) => {
+            lines.push(field("ATH", format!("{:.4} {}", ath.ath, currency)));
+            lines.push(field("ATH date", date_part(&ath.ath_date).to_string()));
+            if let Some(price) = detail.price.filter(|_| ath.ath > 0.0) {
+                lines.push(field("From ATH", format!("{:.2}%", (price.value / ath.ath - 1.0) * 100.0)));
+            }
+            lines.push(field("ATL", format!("{:.4} {}", ath.atl, currency)));
+            lines.push(field("ATL date", date_part(&ath.atl_date).to_string()));
+        }
+        None => lines.push(Line::from("No all time data, run update-all-time-data").italic()),
+    }
+
+    let paragraph = Paragraph::new(lines).block(Block::bordered().title(format!(" {} ", detail.token)));
+    frame.render_widget(paragraph, area);
+}
+
+fn date_part(date: &str) -> &str {
+    date.split('T').next().unwrap_or(date)
+}
+
+// Sparkline of the stored prices, scaled between the lowest and the highest price
+fn draw_history(frame: &mut Frame, detail: &TokenDetail, area: Rect) {
+    let min = detail.history.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = detail.history.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if detail.history.is_empty() {
+        frame.render_widget(Paragraph::new("No stored prices").block(Block::bordered().title(" Price history ")), area);
+        return;
+    }
+
+    // Only the latest prices fit into the pane
+    let width = area.width.saturating_sub(2) as usize;
+    let data: Vec<u64> = detail.history
+        .iter()
+        .skip(detail.history.len().saturating_sub(width))
+        .map(|value| if max > min { ((value - min) / (max - min) * 100.0) as u64 + 1 } else { 1 })
+        .collect();
+    let title = format!(" Price history {:.2} - {:.2} ", min, max);
+    let sparkline = Sparkline::default()
+        .block(Block::bordered().title(title))
+        .data(&data)
+        .style(Style::new().fg(Color::Yellow));
+    frame.render_widget(sparkline, area);
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let left = if app.searching {
+        Line::from(vec![Span::from(" Filter: ").bold(), Span::from(format!("{}_", app.search))])
+    } else {
+        let filter = if app.search.is_empty() { String::new() } else { format!(" Filter: {} |", app.search) };
+        Line::from(format!("{} [/] filter [1-6] sort [o] order [g] group [c] currency [tab] pane [r] refresh [q] quit", filter))
+    };
+    let right = match (&app.message, app.refreshing, app.updated) {
+        (Some(message), _, _) => Line::styled(message.clone(), Style::new().fg(Color::Red)),
+        (None, true, _) => Line::from("Refreshing..."),
+        (None, false, Some(updated)) => Line::from(format!("Updated {} ", updated.format("%H:%M:%S"))),
+        (None, false, None) => Line::default(),
+    };
+
+    let [left_area, right_area] = Layout::horizontal([Constraint::Min(0), Constraint::Length(right.width() as u16 + 1)]).areas(area);
+    frame.render_widget(Paragraph::new(left), left_area);
+    frame.render_widget(Paragraph::new(right.right_aligned()), right_area);
+}
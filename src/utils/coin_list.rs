@@ -1,35 +1,417 @@
 use std::collections::HashMap;
+use std::io::{ self, BufRead, Write };
+use std::path::PathBuf;
 
-use crate::{client::coingecko::Coin, coins::filestore::write_data_json_to_file, config::{coins::read_default_coins_config, read_json_config}, Result};
+use tracing::{ debug, info, warn };
 
-pub fn update_coins_with_list_file() -> Result<()> {
-    println!("Updating coins with ATH data");
-    
-    let coingecko_list_coins = read_json_config::<Vec<Coin>>("data/list.json")?;
+use crate::{
+    client::coingecko::{ Coin, CoinGeckoClient, MARKETS_PAGE_SIZE },
+    config::{
+        coins::{ read_default_coins_config, write_default_coins_config, AthProviderEnum, CoinDef, PriceProviderEnum },
+        read_json_config,
+        paths::Paths,
+        wallets::read_default_wallets_config,
+    },
+    model::Currency,
+    provider::get_price_provider,
+    Result,
+};
 
-    let mut coins_config = read_default_coins_config()?;
-    let mut not_found_coins = Vec::new();
+const TOKEN_ID_KEY: &str = "token_id";
 
-    for coin in coins_config.coins.iter_mut() {
-        let coin_code = &coin.code;
-        let coingecko_coin = coingecko_list_coins.iter().find(|c| c.symbol.to_uppercase() == *coin_code);
-        match coingecko_coin {
-            None => {
-                println!("Coin {} not found in list", coin_code);
-                not_found_coins.push(coin_code);
-            },
-            Some(c) => {
-                println!("Coin {} found in list", coin_code);
-                let mut ath_data = HashMap::new();
-                ath_data.insert("token_id".to_string(), c.id.clone()); 
-                coin.ath_provider_data = Some(ath_data);
+// The largest candidate is picked automatically when its market cap is at least this times larger than the second one
+const MARKET_CAP_DOMINANCE: f64 = 10.0;
+
+// Price providers which are asked for new coins, in the order of preference
+const PROBED_PRICE_PROVIDERS: [PriceProviderEnum; 2] = [PriceProviderEnum::CryptoCompare, PriceProviderEnum::CoinAPI];
+
+// How the ids of the coins are resolved
+pub struct ResolveIdsOptions {
+    // Resolve also the coins which already have the id
+    pub all: bool,
+    // Ask for ambiguous coins on the terminal
+    pub interactive: bool,
+    // Pick the candidate with the largest market cap for ambiguous coins
+    pub pick_top: bool,
+    // Ids set by the user, code to id
+    pub preferred: Vec<(String, String)>,
+    // Coin list downloaded from CoinGecko, instead of calling the api
+    pub list_file: Option<PathBuf>,
+    // Write the changes into the coins config, otherwise only the preview is shown
+    pub write: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub id: String,
+    pub name: String,
+    pub market_cap: f64,
+}
+
+#[derive(Debug)]
+pub enum Resolution {
+    Resolved(String),
+    Ambiguous(Vec<Candidate>),
+    NotFound,
+}
+
+// Resolves CoinGecko ids of the coins in the coins config and shows the changes.
+// The config is written only with the write option.
+pub async fn resolve_coin_ids(paths: &Paths, options: &ResolveIdsOptions) -> Result<()> {
+    let mut coins_config = read_default_coins_config(paths)?;
+    let coin_list = read_coin_list(options.list_file.as_ref()).await?;
+    info!(coins = coin_list.len(), "Coin list loaded");
+
+    let to_resolve: Vec<&CoinDef> = coins_config.coins
+        .iter()
+        .filter(|coin_def| options.all || get_token_id(coin_def).is_none())
+        .collect();
+
+    let candidates_by_code: HashMap<String, Vec<&Coin>> = to_resolve
+        .iter()
+        .map(|coin_def| {
+            let candidates = coin_list
+                .iter()
+                .filter(|coin| coin.symbol.eq_ignore_ascii_case(&coin_def.code))
+                .collect();
+            (coin_def.code.clone(), candidates)
+        })
+        .collect();
+
+    // Market caps are needed only when there is more than one candidate
+    let ambiguous_ids: Vec<String> = candidates_by_code
+        .values()
+        .filter(|candidates| candidates.len() > 1)
+        .flat_map(|candidates| candidates.iter().map(|coin| coin.id.clone()))
+        .collect();
+    let market_caps = get_market_caps(&ambiguous_ids).await;
+
+    let mut changes: Vec<(String, Option<String>, String)> = Vec::new();
+    let mut unresolved: Vec<(String, Resolution)> = Vec::new();
+
+    for coin_def in to_resolve {
+        let current = get_token_id(coin_def);
+        let preferred = options.preferred
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(&coin_def.code))
+            .map(|(_, id)| id.clone());
+
+        let resolution = match preferred {
+            Some(id) => Resolution::Resolved(id),
+            None => resolve_coin_id(coin_def, &candidates_by_code[&coin_def.code], &market_caps),
+        };
+
+        let resolution = match resolution {
+            Resolution::Ambiguous(candidates) if current.as_ref().is_some_and(|id| candidates.iter().any(|c| c.id == *id)) => {
+                // Keep the id which is already set, when it is one of the candidates
+                Resolution::Resolved(current.clone().unwrap_or_default())
+            }
+            Resolution::Ambiguous(candidates) if options.pick_top => Resolution::Resolved(candidates[0].id.clone()),
+            Resolution::Ambiguous(candidates) if options.interactive => {
+                match choose_candidate(&coin_def.code, &candidates)? {
+                    Some(id) => Resolution::Resolved(id),
+                    None => Resolution::Ambiguous(candidates),
+                }
+            }
+            resolution => resolution,
+        };
+
+        match resolution {
+            Resolution::Resolved(id) if current.as_ref() != Some(&id) => {
+                changes.push((coin_def.code.clone(), current, id));
             }
-        } 
+            Resolution::Resolved(_) => {
+                debug!(coin = %coin_def.code, "Id is not changed");
+            }
+            resolution => unresolved.push((coin_def.code.clone(), resolution)),
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    write_preview(&mut out, &changes, &unresolved)?;
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+    if !options.write {
+        writeln!(out, "Dry run, use --write to update the coins config")?;
+        return Ok(());
     }
 
-    println!("Writing updated coins config");
-    println!("Not found coins: {:?}", not_found_coins);
-    write_data_json_to_file("data/list_new.json", &coins_config)?;
+    for (code, _, id) in changes.iter() {
+        if let Some(coin_def) = coins_config.coins.iter_mut().find(|coin_def| coin_def.code == *code) {
+            coin_def.ath_provider_data
+                .get_or_insert_with(HashMap::new)
+                .insert(TOKEN_ID_KEY.to_string(), id.clone());
+        }
+    }
+    write_default_coins_config(paths, &coins_config)?;
+    info!(changes = changes.len(), "Coins config updated");
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Adds the coins from the wallets which are missing in the coins config.
+// The price provider is the first one which returns a price for the coin, the CoinGecko id is resolved
+// from the coin list. Coins without a price provider are only reported.
+pub async fn sync_coins(paths: &Paths, list_file: Option<&PathBuf>, write: bool) -> Result<()> {
+    let mut coins_config = read_default_coins_config(paths)?;
+    let wallets = read_default_wallets_config(paths)?;
+
+    let missing = wallets.missing_coins(&coins_config);
+    let mut out = io::stdout().lock();
+    if missing.is_empty() {
+        writeln!(out, "All coins from wallets are in the coins config")?;
+        return Ok(());
+    }
+    info!(?missing, "Coins missing in the coins config");
+
+    let coin_list = match read_coin_list(list_file).await {
+        Ok(coin_list) => coin_list,
+        Err(e) => {
+            warn!(error = %e, "Cannot get coin list, ids are not resolved");
+            Vec::new()
+        }
+    };
+
+    let mut added = Vec::new();
+    for code in missing {
+        let Some(price_provider) = probe_price_provider(&code).await else {
+            writeln!(out, "! {} is not listed by any price provider", code)?;
+            continue;
+        };
+
+        let mut coin_def = CoinDef {
+            code: code.clone(),
+            name: None,
+            categories: Vec::new(),
+            aliases: Vec::new(),
+            price_provider,
+            price_provider_data: None,
+            ath_provider: AthProviderEnum::CoinGecko,
+            ath_provider_data: None,
+        };
+
+        let candidates: Vec<&Coin> = coin_list
+            .iter()
+            .filter(|coin| coin.symbol.eq_ignore_ascii_case(&code))
+            .collect();
+        let candidate_ids: Vec<String> = candidates.iter().map(|coin| coin.id.clone()).collect();
+        let market_caps = if candidates.len() > 1 { get_market_caps(&candidate_ids).await } else { HashMap::new() };
+
+        match resolve_coin_id(&coin_def, &candidates, &market_caps) {
+            Resolution::Resolved(id) => {
+                coin_def.name = candidates.iter().find(|coin| coin.id == id).map(|coin| coin.name.clone());
+                coin_def.ath_provider_data = Some(HashMap::from([(TOKEN_ID_KEY.to_string(), id)]));
+            }
+            resolution => {
+                debug!(coin = %code, ?resolution, "CoinGecko id not resolved");
+            }
+        }
+
+        writeln!(
+            out,
+            "+ {} price provider: {:?}, {}: {}",
+            code,
+            coin_def.price_provider,
+            TOKEN_ID_KEY,
+            get_token_id(&coin_def).unwrap_or_else(|| "unresolved, run coins resolve-ids".to_string())
+        )?;
+        added.push(coin_def);
+    }
+
+    if added.is_empty() {
+        return Ok(());
+    }
+    if !write {
+        writeln!(out, "Dry run, use --write to update the coins config")?;
+        return Ok(());
+    }
+
+    let count = added.len();
+    coins_config.coins.append(&mut added);
+    write_default_coins_config(paths, &coins_config)?;
+    info!(added = count, "Coins config updated");
+
+    Ok(())
+}
+
+// Returns the first provider which has the price of the coin
+async fn probe_price_provider(code: &str) -> Option<PriceProviderEnum> {
+    for provider_id in PROBED_PRICE_PROVIDERS {
+        let provider = get_price_provider(&provider_id);
+        match provider.get_price(code, &None, &[Currency::USD]).await {
+            Ok(prices) if prices.iter().any(|price| price.coin == code) => {
+                debug!(coin = code, provider = ?provider_id, "Price found");
+                return Some(provider_id);
+            }
+            Ok(_) => debug!(coin = code, provider = ?provider_id, "No price"),
+            Err(e) => debug!(coin = code, provider = ?provider_id, error = %e, "No price"),
+        }
+    }
+    None
+}
+
+// Resolves the id from the candidates with the same symbol.
+// The name hint of the coin narrows the candidates, the rest are ranked by market cap.
+pub fn resolve_coin_id(coin_def: &CoinDef, candidates: &[&Coin], market_caps: &HashMap<String, f64>) -> Resolution {
+    let mut candidates: Vec<&Coin> = candidates.to_vec();
+
+    if let Some(name) = coin_def.name.as_ref() {
+        let exact: Vec<&Coin> = candidates
+            .iter()
+            .filter(|coin| coin.name.eq_ignore_ascii_case(name))
+            .cloned()
+            .collect();
+        let name_lowercase = name.to_lowercase();
+        let similar: Vec<&Coin> = candidates
+            .iter()
+            .filter(|coin| coin.name.to_lowercase().contains(&name_lowercase))
+            .cloned()
+            .collect();
+        if !exact.is_empty() {
+            candidates = exact;
+        } else if !similar.is_empty() {
+            candidates = similar;
+        }
+    }
+
+    let mut ranked: Vec<Candidate> = candidates
+        .iter()
+        .map(|coin| Candidate {
+            id: coin.id.clone(),
+            name: coin.name.clone(),
+            market_cap: market_caps.get(&coin.id).cloned().unwrap_or(0.0),
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.market_cap.total_cmp(&a.market_cap).then_with(|| a.id.cmp(&b.id)));
+
+    match ranked.as_slice() {
+        [] => Resolution::NotFound,
+        [single] => Resolution::Resolved(single.id.clone()),
+        [first, second, ..] if first.market_cap > 0.0 && first.market_cap >= second.market_cap * MARKET_CAP_DOMINANCE => {
+            Resolution::Resolved(first.id.clone())
+        }
+        _ => Resolution::Ambiguous(ranked),
+    }
+}
+
+fn get_token_id(coin_def: &CoinDef) -> Option<String> {
+    coin_def.ath_provider_data.as_ref()?.get(TOKEN_ID_KEY).cloned()
+}
+
+async fn read_coin_list(list_file: Option<&PathBuf>) -> Result<Vec<Coin>> {
+    match list_file {
+        Some(list_file) => read_json_config(list_file.to_str().ok_or("Invalid list file name")?),
+        None => CoinGeckoClient::new().list_coins().await,
+    }
+}
+
+// Market caps of the coins, coins without market data are left out.
+// Errors are only logged, the coins stay ambiguous without the market caps.
+async fn get_market_caps(token_ids: &[String]) -> HashMap<String, f64> {
+    let client = CoinGeckoClient::new();
+    let mut market_caps = HashMap::new();
+    for page in token_ids.chunks(MARKETS_PAGE_SIZE) {
+        match client.get_coins_markets(page.to_vec(), "usd").await {
+            Ok(markets) => {
+                market_caps.extend(markets.into_iter().map(|market| (market.id, market.market_cap)));
+            }
+            Err(e) => warn!(error = %e, "Cannot get market caps"),
+        }
+    }
+    market_caps
+}
+
+fn choose_candidate(code: &str, candidates: &[Candidate]) -> Result<Option<String>> {
+    eprintln!("{} has more candidates:", code);
+    for (index, candidate) in candidates.iter().enumerate() {
+        eprintln!("  {}) {} - {} (market cap {:.0})", index + 1, candidate.id, candidate.name, candidate.market_cap);
+    }
+    eprint!("Choose 1-{}, empty to skip: ", candidates.len());
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    let index: usize = answer.parse().map_err(|_| format!("Invalid choice {}", answer))?;
+    candidates
+        .get(index.wrapping_sub(1))
+        .map(|candidate| Some(candidate.id.clone()))
+        .ok_or_else(|| format!("Invalid choice {}", answer).into())
+}
+
+fn write_preview(
+    out: &mut impl Write,
+    changes: &[(String, Option<String>, String)],
+    unresolved: &[(String, Resolution)]
+) -> io::Result<()> {
+    for (code, current, id) in changes {
+        writeln!(out, "{}", code)?;
+        if let Some(current) = current {
+            writeln!(out, "-  {}: {}", TOKEN_ID_KEY, current)?;
+        }
+        writeln!(out, "+  {}: {}", TOKEN_ID_KEY, id)?;
+    }
+
+    for (code, resolution) in unresolved {
+        match resolution {
+            Resolution::NotFound => writeln!(out, "! {} not found in the coin list", code)?,
+            Resolution::Ambiguous(candidates) => {
+                let ids: Vec<&str> = candidates.iter().map(|candidate| candidate.id.as_str()).collect();
+                writeln!(out, "? {} is ambiguous: {}", code, ids.join(", "))?;
+            }
+            Resolution::Resolved(_) => {}
+        }
+    }
+
+    writeln!(out, "{} changes, {} unresolved", changes.len(), unresolved.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coin(id: &str, symbol: &str, name: &str) -> Coin {
+        Coin {
+            id: id.to_string(),
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    fn coin_def(code: &str, name: Option<&str>) -> CoinDef {
+        serde_json::from_value(serde_json::json!({ "code": code, "name": name })).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_coin_id() {
+        let uni = coin("uniswap", "uni", "Uniswap");
+        let fake = coin("uni-fake", "uni", "Universe");
+        let other = coin("unicorn", "uni", "Unicorn Token");
+        let candidates = vec![&uni, &fake, &other];
+        let mut market_caps = HashMap::new();
+        market_caps.insert("uniswap".to_string(), 5_000_000_000.0);
+        market_caps.insert("uni-fake".to_string(), 1_000_000.0);
+
+        let resolution = resolve_coin_id(&coin_def("UNI", None), &candidates, &market_caps);
+        assert!(matches!(resolution, Resolution::Resolved(id) if id == "uniswap"));
+
+        let resolution = resolve_coin_id(&coin_def("UNI", Some("unicorn")), &candidates, &market_caps);
+        assert!(matches!(resolution, Resolution::Resolved(id) if id == "unicorn"));
+
+        market_caps.insert("unicorn".to_string(), 1_000_000_000.0);
+        let resolution = resolve_coin_id(&coin_def("UNI", None), &candidates, &market_caps);
+        let Resolution::Ambiguous(ranked) = resolution else {
+            panic!("Expected ambiguous resolution");
+        };
+        assert_eq!(ranked[0].id, "uniswap");
+        assert_eq!(ranked[1].id, "unicorn");
+
+        let resolution = resolve_coin_id(&coin_def("UNI", None), &[], &market_caps);
+        assert!(matches!(resolution, Resolution::NotFound));
+    }
+}
@@ -9,10 +9,8 @@ pub fn find_subdir_with_name(dir: &path::Path, name: &str) -> Result<Option<Stri
     for entry in read_dir {
         let entry = entry?;
         let path = entry.path();
-        if path.is_dir() {
-            if path.file_name().unwrap() == name {
-                return Ok(Some(path.to_str().unwrap().to_string()));
-            }
+        if path.is_dir() && path.file_name().unwrap() == name {
+            return Ok(Some(path.to_str().unwrap().to_string()));
         }
     }
     Ok(None)
@@ -21,6 +19,7 @@ pub fn find_subdir_with_name(dir: &path::Path, name: &str) -> Result<Option<Stri
 // function returns the latest file in the directory
 // It is assumed that the files are named and the latest file is the one that is first in the sorted list
 // The files have a timestamp in the name so we can sort them alplphabetically
+#[allow(unused)]
 pub fn get_latest_filename(dir_name: &str) -> Result<Option<PathBuf>> {
     let mut entries: Vec<_> = fs::read_dir(dir_name)?
         .filter_map(|entry| entry.ok())
@@ -0,0 +1,58 @@
+use std::{ fs::OpenOptions, io::IsTerminal, path::Path, sync::Mutex };
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{ fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer };
+
+use crate::Result;
+
+// Maps the -v / -q counters to a console log level.
+// Default is WARN, every -v goes one level up (INFO, DEBUG, TRACE), -q shows only errors and -qq nothing.
+pub fn level_from_verbosity(verbose: u8, quiet: u8) -> LevelFilter {
+    if quiet > 0 {
+        return if quiet == 1 { LevelFilter::ERROR } else { LevelFilter::OFF };
+    }
+    match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+// Initializes logging for the application.
+// Logs always go to stderr, so the reports written to stdout stay clean.
+// When RUST_LOG is set, it overrides the console level derived from -v / -q.
+// When log_file is set, the records are appended to the file as JSON lines, at least on INFO level.
+// Without console the records go only to the log file, for the commands drawing on the terminal.
+pub fn init_logging(verbose: u8, quiet: u8, log_file: Option<&Path>, console: bool) -> Result<()> {
+    let console_level = level_from_verbosity(verbose, quiet);
+    let console_filter = match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(_) => EnvFilter::from_default_env(),
+        Err(_) => EnvFilter::default().add_directive(console_level.into()),
+    };
+
+    let console_layer = console.then(|| {
+        fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_ansi(std::io::stderr().is_terminal())
+            .with_target(false)
+            .with_filter(console_filter)
+    });
+
+    let file_layer = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Cannot open log file {}: {}", path.display(), e))?;
+            let file_level = std::cmp::max(console_level, LevelFilter::INFO);
+            Some(fmt::layer().json().with_writer(Mutex::new(file)).with_filter(file_level))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry().with(console_layer).with(file_layer).try_init()?;
+
+    Ok(())
+}
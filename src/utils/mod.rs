@@ -1,2 +1,4 @@
 pub mod file_utils;
-pub mod coin_list;
\ No newline at end of file
+pub mod coin_list;
+pub mod logging;
+pub mod redact;
@@ -0,0 +1,86 @@
+use reqwest::{ header::HeaderMap, Url };
+
+const REDACTED: &str = "***";
+
+// Parts of header or query parameter names which mark the value as a credential
+const SENSITIVE_NAME_PARTS: [&str; 7] = [
+    "key",
+    "token",
+    "secret",
+    "auth",
+    "password",
+    "cookie",
+    "signature",
+];
+
+fn is_sensitive_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SENSITIVE_NAME_PARTS.iter().any(|part| name.contains(part))
+}
+
+// Returns the url as string, with values of credential query parameters replaced by ***
+pub fn redact_url(url: &Url) -> String {
+    if url.query().is_none() {
+        return url.to_string();
+    }
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if is_sensitive_name(&name) { REDACTED.to_string() } else { value.into_owned() };
+            (name.into_owned(), value)
+        })
+        .collect();
+
+    let mut redacted = url.clone();
+    redacted.query_pairs_mut().clear().extend_pairs(pairs);
+    redacted.to_string()
+}
+
+// Returns the headers as name/value pairs, with values of credential headers replaced by ***
+pub fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if is_sensitive_name(name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                value.to_str().unwrap_or("<binary>").to_string()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{ HeaderValue, AUTHORIZATION, CONTENT_TYPE };
+
+    #[test]
+    fn test_redact_url() {
+        let url = Url::parse(
+            "https://api.coingecko.com/api/v3/coins/bitcoin?vs_currency=usd&x-cg-pro-api-key=secret123"
+        ).unwrap();
+        let redacted = redact_url(&url);
+        assert!(!redacted.contains("secret123"));
+        assert!(redacted.contains("vs_currency=usd"));
+        assert!(redacted.contains("x-cg-pro-api-key=***"));
+
+        let url = Url::parse("https://rest.coinapi.io/v1/exchangerate/BTC").unwrap();
+        assert_eq!(redact_url(&url), "https://rest.coinapi.io/v1/exchangerate/BTC");
+    }
+
+    #[test]
+    fn test_redact_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("ApiKey secret123"));
+        headers.insert("X-CoinAPI-Key", HeaderValue::from_static("secret456"));
+
+        let redacted = redact_headers(&headers);
+        assert!(redacted.contains(&("content-type".to_string(), "application/json".to_string())));
+        assert!(redacted.contains(&("authorization".to_string(), "***".to_string())));
+        assert!(redacted.contains(&("x-coinapi-key".to_string(), "***".to_string())));
+    }
+}